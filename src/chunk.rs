@@ -2,7 +2,7 @@ use std::fmt::Display;
 
 use crc::{Crc, CRC_32_ISO_HDLC};
 
-use crate::chunk_type::ChunkType;
+use crate::{chunk_type::ChunkType, error::PngError};
 
 fn calc_crc(chunk_type_bytes: [u8; 4], chunk_data_bytes: &[u8]) -> u32 {
     let bytes: Vec<u8> = chunk_type_bytes
@@ -16,6 +16,19 @@ fn calc_crc(chunk_type_bytes: [u8; 4], chunk_data_bytes: &[u8]) -> u32 {
 
 pub const CRC_32_ISO: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
 
+/// Largest chunk data length allowed by the PNG specification (2^31 - 1).
+pub const MAX_CHUNK_LENGTH: u32 = (1 << 31) - 1;
+
+/// Reads `N` bytes starting at `offset`, failing with `Truncated` instead of panicking.
+pub(crate) fn read_array<const N: usize>(value: &[u8], offset: usize) -> Result<[u8; N], PngError> {
+    offset
+        .checked_add(N)
+        .and_then(|end| value.get(offset..end))
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(PngError::Truncated {
+            offset: value.len(),
+        })
+}
 
 #[derive(Debug, Clone)]
 pub struct Chunk {
@@ -55,6 +68,55 @@ impl Chunk {
         String::from_utf8(self.chunk_data.clone())
     }
 
+    /// Parses exactly one chunk from `value`, rejecting chunks whose data is longer than `max_length`.
+    pub fn try_from_with_max_length(value: &[u8], max_length: u32) -> Result<Chunk, PngError> {
+        let length = u32::from_be_bytes(read_array(value, 0)?);
+        if length > max_length {
+            return Err(PngError::ChunkTooLarge {
+                length,
+                max: max_length,
+                offset: 0,
+            });
+        }
+
+        let chunk_bytes: [u8; 4] = read_array(value, 4)?;
+        let chunk_type = ChunkType::try_from(chunk_bytes)?;
+
+        let data_end = (length as usize)
+            .checked_add(8)
+            .ok_or(PngError::Truncated {
+                offset: value.len(),
+            })?;
+        let chunk_data = value
+            .get(8..data_end)
+            .ok_or(PngError::Truncated {
+                offset: value.len(),
+            })?
+            .to_vec();
+        let crc_iso = u32::from_be_bytes(read_array(value, data_end)?);
+
+        let chunk_end = data_end + 4;
+        if value.len() > chunk_end {
+            return Err(PngError::TrailingData { offset: chunk_end });
+        }
+
+        let actual_crc = calc_crc(chunk_bytes, &chunk_data);
+        if crc_iso != actual_crc {
+            return Err(PngError::CrcMismatch {
+                chunk_type,
+                expected: crc_iso,
+                actual: actual_crc,
+                offset: 0,
+            });
+        }
+
+        Ok(Chunk {
+            chunk_type,
+            chunk_data,
+            crc_iso,
+        })
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         let len = self.length();
         len.to_be_bytes()
@@ -68,25 +130,10 @@ impl Chunk {
 }
 
 impl TryFrom<&[u8]> for Chunk {
-    type Error = String;
+    type Error = PngError;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        let length = u32::from_be_bytes(value[0..4].try_into().unwrap());
-        let chunk_bytes: [u8; 4] = value[4..8].try_into().unwrap();
-        let chunk_type = ChunkType::try_from(chunk_bytes).or(Err("create ChunkType from bytes"))?;
-
-        let chunk_data = Vec::from(&value[8..(length + 8) as usize]);
-        let crc_iso = u32::from_be_bytes(
-            value[(length + 8) as usize..]
-                .try_into()
-                .or(Err("crc from_be_bytes"))?,
-        );
-
-        if crc_iso != calc_crc(chunk_bytes, &chunk_data) {
-            return Err(String::from("corrupted crc!"));
-        }
-
-        Ok(Chunk { chunk_type, chunk_data, crc_iso })
+        Chunk::try_from_with_max_length(value, MAX_CHUNK_LENGTH)
     }
 }
 
@@ -95,9 +142,7 @@ impl Display for Chunk {
         write!(
             f,
             "chunk_type: {}, chunk_data: {:?}, crc: {}",
-            self.chunk_type,
-            self.chunk_data,
-            self.crc_iso
+            self.chunk_type, self.chunk_data, self.crc_iso
         )
     }
 }
@@ -208,7 +253,64 @@ mod tests {
 
         let chunk = Chunk::try_from(chunk_data.as_ref());
 
-        assert!(chunk.is_err());
+        assert!(matches!(
+            chunk,
+            Err(PngError::CrcMismatch {
+                expected: 2882656333,
+                actual: 2882656334,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_truncated_chunk_from_bytes() {
+        let bytes = testing_chunk().as_bytes();
+
+        for len in 0..bytes.len() {
+            let chunk = Chunk::try_from(&bytes[..len]);
+            assert!(matches!(chunk, Err(PngError::Truncated { offset }) if offset == len));
+        }
+    }
+
+    #[test]
+    fn test_chunk_with_trailing_data() {
+        let mut bytes = testing_chunk().as_bytes();
+        bytes.push(0);
+
+        let chunk = Chunk::try_from(bytes.as_ref());
+
+        assert!(matches!(chunk, Err(PngError::TrailingData { offset: 54 })));
+    }
+
+    #[test]
+    fn test_chunk_length_over_limit() {
+        #[rustfmt::skip]
+        let bytes = [
+            255, 255, 255, 255, // length
+            82, 117, 83, 116,   // Chunk Type
+        ];
+
+        let chunk = Chunk::try_from(bytes.as_ref());
+        assert!(matches!(
+            chunk,
+            Err(PngError::ChunkTooLarge {
+                length: u32::MAX,
+                max: MAX_CHUNK_LENGTH,
+                ..
+            })
+        ));
+
+        let bytes = testing_chunk().as_bytes();
+        let chunk = Chunk::try_from_with_max_length(bytes.as_ref(), 41);
+        assert!(matches!(
+            chunk,
+            Err(PngError::ChunkTooLarge {
+                length: 42,
+                max: 41,
+                ..
+            })
+        ));
     }
 
     #[test]
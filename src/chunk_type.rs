@@ -1,5 +1,7 @@
 use std::{fmt::Display, str::FromStr};
 
+use crate::error::PngError;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ChunkType {
     data: [u8; 4],
@@ -10,33 +12,45 @@ impl ChunkType {
         self.data
     }
 
-    fn is_valid(&self) -> bool {
+    pub fn is_valid(&self) -> bool {
         self.is_reserved_bit_valid()
     }
 
-    fn is_critical(&self) -> bool {
+    pub fn is_critical(&self) -> bool {
         (self.data[0] >> 5) & 1 == 0
     }
 
-    fn is_public(&self) -> bool {
+    pub fn is_public(&self) -> bool {
         (self.data[1] >> 5) & 1 == 0
     }
 
-    fn is_reserved_bit_valid(&self) -> bool {
+    pub fn is_reserved_bit_valid(&self) -> bool {
         (self.data[2] >> 5) & 1 == 0
     }
 
-    fn is_safe_to_copy(&self) -> bool {
+    pub fn is_safe_to_copy(&self) -> bool {
         (self.data[3] >> 5) & 1 == 1
     }
 
+    /// Like `from_str`, but also rejects types with the reserved bit set
+    /// (lowercase third letter), as required by the PNG specification.
+    pub fn from_str_strict(s: &str) -> Result<ChunkType, PngError> {
+        let chunk_type = ChunkType::from_str(s)?;
+        if !chunk_type.is_valid() {
+            return Err(PngError::InvalidChunkType(String::from(
+                "reserved bit is set, third letter must be upper case",
+            )));
+        }
+        Ok(chunk_type)
+    }
+
     fn validate_bytes(&self) -> bool {
         self.data.iter().all(|x| x.is_ascii_alphabetic())
     }
 }
 
 impl TryFrom<[u8; 4]> for ChunkType {
-    type Error = String;
+    type Error = PngError;
 
     fn try_from(value: [u8; 4]) -> Result<Self, Self::Error> {
         let chunk_type = ChunkType { data: value };
@@ -45,27 +59,22 @@ impl TryFrom<[u8; 4]> for ChunkType {
             return Ok(chunk_type);
         }
 
-        Err(String::from(
+        Err(PngError::InvalidChunkType(String::from(
             "chunk type have to be lower or upper case alphabetic",
-        ))
+        )))
     }
 }
 
 impl FromStr for ChunkType {
-    type Err = String;
+    type Err = PngError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() != 4 {
-            return Err(String::from("chunk type must have 4 bytes"));
-        }
-
-        let data: [u8; 4] = s
-            .as_bytes()
-            .windows(4)
-            .next()
-            .ok_or(String::from("chunk type must have 4 bytes"))?
-            .try_into()
-            .or(Err(String::from("cannot conver &[u8] to [u8;4]")))?;
+        let data: [u8; 4] =
+            s.as_bytes()
+                .try_into()
+                .or(Err(PngError::InvalidChunkType(String::from(
+                    "chunk type must have 4 bytes",
+                ))))?;
 
         let chunk_type = ChunkType { data };
 
@@ -73,9 +82,9 @@ impl FromStr for ChunkType {
             return Ok(chunk_type);
         }
 
-        Err(String::from(
+        Err(PngError::InvalidChunkType(String::from(
             "chunk type have to be lower or upper case alphabetic",
-        ))
+        )))
     }
 }
 
@@ -169,6 +178,16 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    pub fn test_chunk_type_from_str_strict() {
+        assert!(ChunkType::from_str_strict("RuSt").is_ok());
+        assert!(matches!(
+            ChunkType::from_str_strict("Rust"),
+            Err(PngError::InvalidChunkType(_))
+        ));
+        assert!(ChunkType::from_str_strict("Ru1t").is_err());
+    }
+
     #[test]
     pub fn test_chunk_type_string() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
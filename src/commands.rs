@@ -1,55 +1,1785 @@
-use std::{fs, path::Path, str::FromStr};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::Serialize;
 
-pub fn encode(path: &str, chunk_type: &str, chunk_data: &str) -> Result<(), String> {
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    compression::Codec,
+    crypto::{self, Decryption, Encryption, Identity, Recipient},
+    error::PngError,
+    ihdr::Ihdr,
+    lint::Violation,
+    payload::{
+        reassemble, split_payload, FileEnvelope, Fragment, Payload, DEFAULT_MAX_CHUNK_LENGTH,
+    },
+    png::{ChunkPosition, Png},
+    reader::ChunkReader,
+    signature::{ChunkSignature, SignatureScope, SigningKey, VerifyingKey, SIGNATURE_CHUNK_TYPE},
+    stego,
+    strip::{self, StripPreset, StrippedChunk},
+    text::{keyword_of, text_of, TextChunk},
+};
+
+/// Input path that reads the png from stdin.
+pub const STDIN_PATH: &str = "-";
+
+/// Random temp file names tried by `--in-place` before giving up.
+const TEMP_FILE_ATTEMPTS: usize = 8;
+
+/// Destination of a png modified by a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Output {
+    Path(PathBuf),
+    /// Like `Path`, but fails instead of overwriting an existing file.
+    NewPath(PathBuf),
+    /// Atomically replaces the input file.
+    InPlace,
+    Stdout,
+}
+
+impl Output {
+    /// Parses an `--output` argument, where `-` means stdout.
+    pub fn from_arg(arg: &str) -> Output {
+        match arg {
+            "-" => Output::Stdout,
+            path => Output::Path(PathBuf::from(path)),
+        }
+    }
+}
+
+/// How `encode` stores its data.
+pub struct PayloadOptions {
+    pub position: ChunkPosition,
+    /// Applied before encryption, ciphertext doesn't compress.
+    pub compression: Option<Codec>,
+    pub encryption: Option<Encryption>,
+    /// Longer data is split into several chunks of the same type.
+    pub max_chunk_length: usize,
+}
+
+impl Default for PayloadOptions {
+    fn default() -> Self {
+        PayloadOptions {
+            position: ChunkPosition::default(),
+            compression: None,
+            encryption: None,
+            max_chunk_length: DEFAULT_MAX_CHUNK_LENGTH,
+        }
+    }
+}
+
+/// Stores `payload` behind a payload header in a new chunk, or in consecutive
+/// fragment chunks when it's longer than `options.max_chunk_length`.
+pub fn encode(
+    path: &str,
+    chunk_type: &str,
+    payload: &Payload,
+    options: &PayloadOptions,
+    output: &Output,
+) -> Result<(), PngError> {
     let mut png = open_as_png(path)?;
+    encode_payload_png(&mut png, chunk_type, payload, options)?;
 
-    let chunk_type: ChunkType = ChunkType::from_str(chunk_type)?;
-    let chunk = Chunk::new(chunk_type, chunk_data.into());
-    png.append_chunk(chunk);
+    write_output(&png, path, output)
+}
 
-    let p = Path::new(path);
+/// Streams chunks from `path` and stops at the first `chunk_type` chunk,
+/// decrypting its data when `decryption` is given. A fragmented payload is
+/// reassembled first, a compressed one is decompressed up to
+/// `max_decompressed_length` bytes.
+pub fn decode(
+    path: &str,
+    chunk_type: &str,
+    decryption: Option<&Decryption>,
+    max_decompressed_length: usize,
+) -> Result<String, PngError> {
+    let chunk = find_chunk(path, chunk_type)?;
+    decoded_payload(&chunk, decryption, max_decompressed_length)
+}
+
+/// Hides `payload` in the least significant bits of the pixels instead of a
+/// chunk, compressed and encrypted like `encode` does.
+pub fn hide(
+    path: &str,
+    payload: &Payload,
+    compression: Option<Codec>,
+    encryption: Option<&Encryption>,
+    output: &Output,
+) -> Result<(), PngError> {
+    let mut png = open_as_png(path)?;
+    hide_png(&mut png, payload, compression, encryption)?;
 
-    fs::write(p.with_file_name("encoded.png"), png.as_bytes())
-        .map_err(|e| format!("write to file {}: {}", path, e))
+    write_output(&png, path, output)
 }
 
-pub fn decode(path: &str, chunk_type: &str) -> Result<String, String> {
-    let png = open_as_png(path)?;
-    let found_chunk = png
-        .chunk_by_type(chunk_type)
-        .ok_or("chunk not found".to_string())?;
+/// Recovers data hidden with `hide`, decrypted and decompressed.
+pub fn reveal(
+    path: &str,
+    decryption: Option<&Decryption>,
+    max_decompressed_length: usize,
+) -> Result<Payload, PngError> {
+    reveal_png(&open_as_png(path)?, decryption, max_decompressed_length)
+}
 
-    found_chunk
-        .data_as_string()
-        .map_err(|e| format!("invalid chunk data: {}", e))
+/// Reads a file to embed with `encode`, wrapped in a `FileEnvelope` recording
+/// its name, MIME type and size unless `raw` is set.
+pub fn read_payload_file(path: &str, raw: bool) -> Result<Payload, PngError> {
+    let data = fs::read(path).map_err(|source| PngError::Io {
+        path: path.to_string(),
+        source,
+    })?;
+
+    if raw {
+        return Ok(Payload::Message(data));
+    }
+    Ok(Payload::File(FileEnvelope::from_file(
+        Path::new(path),
+        data,
+    )))
 }
 
-pub fn validate(chunk_type: &str) -> Result<(), String> {
-    if let Err(e) = ChunkType::from_str(chunk_type) {
-        return Err(format!("invalid chunk_type: {}", e));
+/// Writes raw data of the first `chunk_type` chunk to `out`, decrypted when
+/// `decryption` is given and decompressed when it was compressed. Data in a `FileEnvelope` is unwrapped, and if `out` is
+/// a directory the file keeps its original name. `-` writes to stdout.
+///
+/// Returns where the data went and the envelope, if there was one.
+pub fn extract(
+    path: &str,
+    chunk_type: &str,
+    decryption: Option<&Decryption>,
+    max_decompressed_length: usize,
+    out: &str,
+) -> Result<(String, FileEnvelope), PngError> {
+    let chunk = find_chunk(path, chunk_type)?;
+    let payload = open_payload(chunk.data(), decryption, max_decompressed_length)?;
+    save_payload(payload, out)
+}
+
+/// Writes `payload` returned by `reveal` to `out` like `extract` does.
+pub fn save_payload(payload: Payload, out: &str) -> Result<(String, FileEnvelope), PngError> {
+    let envelope = payload.into_file();
+
+    if out == STDIN_PATH {
+        io::stdout()
+            .write_all(&envelope.data)
+            .map_err(|source| PngError::Io {
+                path: String::from("<stdout>"),
+                source,
+            })?;
+        return Ok((String::from("<stdout>"), envelope));
     }
+
+    let mut out_path = PathBuf::from(out);
+    if out_path.is_dir() {
+        // Only the last component is used, a crafted name can't escape `out`.
+        let file_name = envelope
+            .file_name
+            .as_deref()
+            .and_then(|name| Path::new(name).file_name())
+            .ok_or(PngError::InvalidOutput(format!(
+                "{} is a directory and the payload has no file name",
+                out
+            )))?;
+        out_path.push(file_name);
+    }
+    let out_str = out_path.display().to_string();
+    fs::write(&out_path, &envelope.data).map_err(|source| PngError::Io {
+        path: out_str.clone(),
+        source,
+    })?;
+    Ok((out_str, envelope))
+}
+
+/// Generates a new identity and writes its key file, refusing to overwrite an
+/// existing one. Returns the public key to share with senders.
+pub fn keygen(output: &Output) -> Result<Recipient, PngError> {
+    let identity = Identity::generate()?;
+    write_key_file(&identity.to_key_file(), output)?;
+    Ok(identity.recipient())
+}
+
+/// Generates a new signing key and writes its key file, refusing to overwrite an
+/// existing one. Returns the public key to verify signatures with.
+pub fn signing_keygen(output: &Output) -> Result<VerifyingKey, PngError> {
+    let key = SigningKey::generate()?;
+    write_key_file(&key.to_key_file(), output)?;
+    Ok(key.verifying_key())
+}
+
+/// Reads a key file written by `keygen`.
+pub fn read_identity(path: &str) -> Result<Identity, PngError> {
+    let key_file = fs::read_to_string(path).map_err(|source| PngError::Io {
+        path: path.to_string(),
+        source,
+    })?;
+    Identity::from_str(&key_file)
+}
+
+/// Reads a key file written by `signing_keygen`.
+pub fn read_signing_key(path: &str) -> Result<SigningKey, PngError> {
+    let key_file = fs::read_to_string(path).map_err(|source| PngError::Io {
+        path: path.to_string(),
+        source,
+    })?;
+    SigningKey::from_str(&key_file)
+}
+
+/// Signs the chunks of `path` covered by `scope` and stores the signature
+/// before `IEND`. Signatures of other keys are kept.
+pub fn sign(
+    path: &str,
+    key: &SigningKey,
+    scope: SignatureScope,
+    output: &Output,
+) -> Result<(), PngError> {
+    let mut png = open_as_png(path)?;
+    sign_png(&mut png, key, scope)?;
+    write_output(&png, path, output)
+}
+
+/// Checks every signature of `path`, or only the ones made by `key`.
+/// Returns the verified signatures.
+pub fn verify(path: &str, key: Option<&VerifyingKey>) -> Result<Vec<ChunkSignature>, PngError> {
+    verify_png(&open_as_png(path)?, key)
+}
+
+/// Replaces an earlier signature of the same key, so re-signing doesn't pile up chunks.
+pub fn sign_png(png: &mut Png, key: &SigningKey, scope: SignatureScope) -> Result<(), PngError> {
+    if scope.include.len() > u8::MAX as usize {
+        return Err(PngError::InvalidSignatureChunk(format!(
+            "at most {} chunk types can be included",
+            u8::MAX
+        )));
+    }
+
+    let verifying_key = key.verifying_key();
+    let chunks: Vec<Chunk> = png
+        .chunks()
+        .into_iter()
+        .filter(|chunk| {
+            ChunkSignature::try_from(*chunk)
+                .map_or(true, |signature| signature.key() != &verifying_key)
+        })
+        .cloned()
+        .collect();
+    *png = Png::from_chunks(chunks);
+
+    let signature = ChunkSignature::sign(&png.chunks(), key, scope);
+    png.insert_before_iend(signature.to_chunk());
     Ok(())
 }
 
-pub fn get_chunks(path: &str) -> Result<Vec<Chunk>, String> {
+/// Malformed signature chunks fail verification, even when they aren't by `key`.
+/// Without `key` each signature is checked against the key embedded in it,
+/// which proves the png is intact but not who signed it.
+pub fn verify_png(png: &Png, key: Option<&VerifyingKey>) -> Result<Vec<ChunkSignature>, PngError> {
+    let chunks = png.chunks();
+    let signatures: Vec<ChunkSignature> = chunks
+        .iter()
+        .filter(|chunk| ChunkSignature::is_signature_chunk(chunk))
+        .map(|chunk| ChunkSignature::try_from(*chunk))
+        .filter(|signature| {
+            signature.as_ref().map_or(true, |signature| {
+                key.is_none_or(|key| signature.key() == key)
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    if signatures.is_empty() {
+        return Err(PngError::ChunkNotFound(match key {
+            Some(key) => format!("{} by {}", SIGNATURE_CHUNK_TYPE, key),
+            None => SIGNATURE_CHUNK_TYPE.to_string(),
+        }));
+    }
+    for signature in &signatures {
+        signature.verify(&chunks)?;
+    }
+    Ok(signatures)
+}
+
+/// Reads only the first chunk of `path` and parses it as `IHDR`.
+pub fn info(path: &str) -> Result<Ihdr, PngError> {
+    let first = open_chunk_reader(path)?
+        .next()
+        .ok_or(PngError::ChunkNotFound(String::from("IHDR")))?
+        .map_err(|e| with_path(e, path))?;
+    Png::from_chunks(vec![first]).header_info()
+}
+
+/// Checks chunk ordering of `path`, failing only when the png itself can't be read.
+pub fn lint(path: &str) -> Result<Vec<Violation>, PngError> {
+    Ok(open_as_png(path)?.validate())
+}
+
+/// Checks `chunk_type` strictly, including the reserved bit.
+pub fn validate(chunk_type: &str) -> Result<ChunkType, PngError> {
+    ChunkType::from_str_strict(chunk_type)
+}
+
+/// Human readable breakdown of the four property bits of `chunk_type`.
+pub fn describe_chunk_type(chunk_type: &ChunkType) -> String {
+    let bit = |set: bool, yes: &str, no: &str| if set { yes.to_string() } else { no.to_string() };
+
+    [
+        format!(
+            "critical: {}",
+            bit(chunk_type.is_critical(), "yes", "no (ancillary)")
+        ),
+        format!(
+            "public: {}",
+            bit(chunk_type.is_public(), "yes", "no (private)")
+        ),
+        format!(
+            "reserved bit: {}",
+            bit(chunk_type.is_reserved_bit_valid(), "valid", "invalid")
+        ),
+        format!(
+            "safe to copy: {}",
+            bit(chunk_type.is_safe_to_copy(), "yes", "no")
+        ),
+    ]
+    .join("\n")
+}
+
+pub fn get_chunks(path: &str) -> Result<Vec<Chunk>, PngError> {
     let png = open_as_png(path)?;
     let chunks = png.chunks().iter().map(|c| (**c).clone()).collect();
     Ok(chunks)
 }
 
-pub fn remove_chunk(path: &str, chunk_type: &str) -> Result<(), String> {
+/// Text encoding of chunk data in `ChunkInfo`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataEncoding {
+    /// Falls back to `Hex` when data isn't valid UTF-8.
+    Utf8,
+    Hex,
+    Base64,
+}
+
+impl FromStr for DataEncoding {
+    type Err = PngError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "utf8" => Ok(DataEncoding::Utf8),
+            "hex" => Ok(DataEncoding::Hex),
+            "base64" => Ok(DataEncoding::Base64),
+            other => Err(PngError::InvalidFormat(other.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListFormat {
+    /// Single JSON array.
+    Json,
+    /// One JSON object per line.
+    Jsonl,
+    Table,
+}
+
+impl FromStr for ListFormat {
+    type Err = PngError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ListFormat::Json),
+            "jsonl" => Ok(ListFormat::Jsonl),
+            "table" => Ok(ListFormat::Table),
+            other => Err(PngError::InvalidFormat(other.to_string())),
+        }
+    }
+}
+
+/// Machine readable description of a single chunk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChunkInfo {
+    pub index: usize,
+    pub offset: usize,
+    #[serde(rename = "type")]
+    pub chunk_type: String,
+    pub length: u32,
+    pub crc: u32,
+    pub critical: bool,
+    pub public: bool,
+    pub reserved_bit_valid: bool,
+    pub safe_to_copy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_encoding: Option<DataEncoding>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+}
+
+impl ChunkInfo {
+    /// Describes `chunk` found at `offset`, with its data encoded if `encoding` is given.
+    pub fn new(
+        index: usize,
+        offset: usize,
+        chunk: &Chunk,
+        encoding: Option<DataEncoding>,
+    ) -> ChunkInfo {
+        let chunk_type = chunk.chunk_type();
+        let (data_encoding, data) = match encoding {
+            Some(encoding) => {
+                let (encoding, data) = encode_data(chunk.data(), encoding);
+                (Some(encoding), Some(data))
+            }
+            None => (None, None),
+        };
+
+        ChunkInfo {
+            index,
+            offset,
+            chunk_type: chunk_type.to_string(),
+            length: chunk.length(),
+            crc: chunk.crc(),
+            critical: chunk_type.is_critical(),
+            public: chunk_type.is_public(),
+            reserved_bit_valid: chunk_type.is_reserved_bit_valid(),
+            safe_to_copy: chunk_type.is_safe_to_copy(),
+            data_encoding,
+            data,
+        }
+    }
+}
+
+/// Streams chunks from `path` and describes each of them.
+pub fn chunk_infos(path: &str, encoding: Option<DataEncoding>) -> Result<Vec<ChunkInfo>, PngError> {
+    let mut reader = open_chunk_reader(path)?;
+    let mut infos = Vec::new();
+
+    loop {
+        let offset = reader.offset();
+        match reader.next() {
+            Some(chunk) => {
+                let chunk = chunk.map_err(|e| with_path(e, path))?;
+                infos.push(ChunkInfo::new(infos.len(), offset, &chunk, encoding));
+            }
+            None => return Ok(infos),
+        }
+    }
+}
+
+pub fn format_chunk_infos(infos: &[ChunkInfo], format: ListFormat) -> String {
+    match format {
+        ListFormat::Json => serde_json::to_string_pretty(infos).expect("ChunkInfo is valid JSON"),
+        ListFormat::Jsonl => infos
+            .iter()
+            .map(|info| serde_json::to_string(info).expect("ChunkInfo is valid JSON"))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ListFormat::Table => {
+            let yes_no = |flag: bool| if flag { "yes" } else { "no" };
+            let mut table = format!(
+                "{:>5} {:>8} {:<4} {:>10} {:<10} {:<8} {:<6} {:<8} {:<12}",
+                "INDEX",
+                "OFFSET",
+                "TYPE",
+                "LENGTH",
+                "CRC",
+                "CRITICAL",
+                "PUBLIC",
+                "RESERVED",
+                "SAFE_TO_COPY"
+            );
+            if infos.iter().any(|info| info.data.is_some()) {
+                table.push_str(" DATA");
+            }
+            for info in infos {
+                table.push_str(&format!(
+                    "\n{:>5} {:>8} {:<4} {:>10} {:#010x} {:<8} {:<6} {:<8} {:<12}",
+                    info.index,
+                    info.offset,
+                    info.chunk_type,
+                    info.length,
+                    info.crc,
+                    yes_no(info.critical),
+                    yes_no(info.public),
+                    yes_no(info.reserved_bit_valid),
+                    yes_no(info.safe_to_copy),
+                ));
+                if let Some(data) = &info.data {
+                    table.push_str(&format!(" {}", data));
+                }
+            }
+            table
+        }
+    }
+}
+
+fn encode_data(data: &[u8], encoding: DataEncoding) -> (DataEncoding, String) {
+    match encoding {
+        DataEncoding::Utf8 => match std::str::from_utf8(data) {
+            Ok(text) => (DataEncoding::Utf8, text.to_string()),
+            Err(_) => encode_data(data, DataEncoding::Hex),
+        },
+        DataEncoding::Hex => (
+            DataEncoding::Hex,
+            data.iter().map(|byte| format!("{:02x}", byte)).collect(),
+        ),
+        DataEncoding::Base64 => (DataEncoding::Base64, BASE64.encode(data)),
+    }
+}
+
+/// Which of the matching chunks `remove_chunks` removes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Occurrence {
+    First,
+    All,
+    /// The chunk at this index among the matching ones, counting from 0.
+    Index(usize),
+}
+
+/// Condition a chunk must meet to be removed, parsed from `keyword=<keyword>`,
+/// `size>N` or `size<N` where size is the length of chunk data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkFilter {
+    /// Text chunks with this keyword.
+    Keyword(String),
+    LargerThan(usize),
+    SmallerThan(usize),
+}
+
+impl ChunkFilter {
+    pub fn matches(&self, chunk: &Chunk) -> bool {
+        let length = chunk.length() as usize;
+        match self {
+            ChunkFilter::Keyword(keyword) => keyword_of(chunk).is_some_and(|k| &k == keyword),
+            ChunkFilter::LargerThan(size) => length > *size,
+            ChunkFilter::SmallerThan(size) => length < *size,
+        }
+    }
+}
+
+impl FromStr for ChunkFilter {
+    type Err = PngError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let size = |value: &str| {
+            value
+                .parse()
+                .map_err(|_| PngError::InvalidFormat(s.to_string()))
+        };
+
+        if let Some(keyword) = s.strip_prefix("keyword=") {
+            Ok(ChunkFilter::Keyword(keyword.to_string()))
+        } else if let Some(value) = s.strip_prefix("size>") {
+            Ok(ChunkFilter::LargerThan(size(value)?))
+        } else if let Some(value) = s.strip_prefix("size<") {
+            Ok(ChunkFilter::SmallerThan(size(value)?))
+        } else {
+            Err(PngError::InvalidFormat(s.to_string()))
+        }
+    }
+}
+
+/// Removes `chunk_type` chunks meeting every filter and returns how many were
+/// removed.
+pub fn remove_chunks(
+    path: &str,
+    chunk_type: &str,
+    occurrence: Occurrence,
+    filters: &[ChunkFilter],
+    output: &Output,
+) -> Result<usize, PngError> {
+    let mut png = open_as_png(path)?;
+    let removed = remove_chunks_png(&mut png, chunk_type, occurrence, filters)?;
+
+    write_output(&png, path, output)?;
+    Ok(removed.len())
+}
+
+/// Removes metadata selected by `preset` from `path`, keeping critical chunks
+/// and every type in `keep`, and returns what was dropped.
+pub fn strip_metadata(
+    path: &str,
+    preset: StripPreset,
+    keep: &[ChunkType],
+    output: &Output,
+) -> Result<Vec<StrippedChunk>, PngError> {
+    let mut png = open_as_png(path)?;
+    let stripped = strip::strip(&mut png, preset, keep);
+
+    write_output(&png, path, output)?;
+    Ok(stripped)
+}
+
+pub fn remove_chunk(path: &str, chunk_type: &str, output: &Output) -> Result<(), PngError> {
+    let mut png = open_as_png(path)?;
+    remove_chunk_png(&mut png, chunk_type)?;
+
+    write_output(&png, path, output)
+}
+
+/// Inserts a new `chunk_type` chunk holding `chunk_data` into an in-memory png.
+pub fn encode_png(
+    png: &mut Png,
+    chunk_type: &str,
+    chunk_data: &[u8],
+    position: ChunkPosition,
+) -> Result<(), PngError> {
+    let chunk_type: ChunkType = ChunkType::from_str(chunk_type)?;
+    let chunk = Chunk::new(chunk_type, chunk_data.to_vec());
+    png.insert_chunk(chunk, position)
+}
+
+/// `encode` on an in-memory png.
+pub fn encode_payload_png(
+    png: &mut Png,
+    chunk_type: &str,
+    payload: &Payload,
+    options: &PayloadOptions,
+) -> Result<(), PngError> {
+    let data = seal_payload(payload, options.compression, options.encryption.as_ref())?;
+    if data.len() <= options.max_chunk_length {
+        return encode_png(png, chunk_type, &data, options.position);
+    }
+
+    let chunk_type = ChunkType::from_str(chunk_type)?;
+    let chunks = split_payload(&data, options.max_chunk_length)?
+        .into_iter()
+        .map(|fragment| Chunk::new(chunk_type.clone(), fragment.to_bytes()))
+        .collect();
+    png.insert_chunks(chunks, options.position)
+}
+
+/// `decode` on an in-memory png.
+pub fn decode_payload_png(
+    png: &Png,
+    chunk_type: &str,
+    decryption: Option<&Decryption>,
+    max_decompressed_length: usize,
+) -> Result<String, PngError> {
+    let chunk = collect_payload(png.chunks().into_iter().cloned().map(Ok), chunk_type)?;
+    decoded_payload(&chunk, decryption, max_decompressed_length)
+}
+
+/// `hide` on an in-memory png.
+pub fn hide_png(
+    png: &mut Png,
+    payload: &Payload,
+    compression: Option<Codec>,
+    encryption: Option<&Encryption>,
+) -> Result<(), PngError> {
+    stego::embed(png, &seal_payload(payload, compression, encryption)?)
+}
+
+/// `reveal` on an in-memory png.
+pub fn reveal_png(
+    png: &Png,
+    decryption: Option<&Decryption>,
+    max_decompressed_length: usize,
+) -> Result<Payload, PngError> {
+    open_payload(&stego::extract(png)?, decryption, max_decompressed_length)
+}
+
+/// Returns data of the first `chunk_type` chunk as UTF-8 string.
+pub fn decode_png(png: &Png, chunk_type: &str) -> Result<String, PngError> {
+    let found_chunk = collect_payload(png.chunks().into_iter().cloned().map(Ok), chunk_type)?;
+
+    decoded_data(&found_chunk)
+}
+
+/// Removes the first `chunk_type` chunk from an in-memory png and returns it.
+pub fn remove_chunk_png(png: &mut Png, chunk_type: &str) -> Result<Chunk, PngError> {
+    png.remove_chunk(chunk_type)
+}
+
+/// Removes `chunk_type` chunks of an in-memory png that meet every filter and
+/// returns them. Fails if none match.
+pub fn remove_chunks_png(
+    png: &mut Png,
+    chunk_type: &str,
+    occurrence: Occurrence,
+    filters: &[ChunkFilter],
+) -> Result<Vec<Chunk>, PngError> {
+    let given_chunk_type = ChunkType::from_str(chunk_type)?;
+    let selected = |chunk: &Chunk| {
+        chunk.chunk_type() == &given_chunk_type && filters.iter().all(|f| f.matches(chunk))
+    };
+
+    let matching: Vec<usize> = png
+        .chunks()
+        .into_iter()
+        .enumerate()
+        .filter(|(_, chunk)| selected(chunk))
+        .map(|(index, _)| index)
+        .collect();
+    let index = match occurrence {
+        Occurrence::All if !matching.is_empty() => return Ok(png.retain(|chunk| !selected(chunk))),
+        Occurrence::All | Occurrence::First => matching.first(),
+        Occurrence::Index(n) => matching.get(n),
+    };
+
+    match index {
+        Some(&index) => Ok(vec![png.remove_at(index)?]),
+        None => Err(PngError::ChunkNotFound(match occurrence {
+            Occurrence::Index(n) => format!("{} at index {}", chunk_type, n),
+            _ => chunk_type.to_string(),
+        })),
+    }
+}
+
+/// Lists every `tEXt` chunk of `path`, failing on malformed ones.
+pub fn text_list(path: &str) -> Result<Vec<TextChunk>, PngError> {
+    let mut texts = Vec::new();
+    for chunk in open_chunk_reader(path)? {
+        let chunk = chunk.map_err(|e| with_path(e, path))?;
+        if TextChunk::is_text_chunk(&chunk) {
+            texts.push(TextChunk::try_from(&chunk)?);
+        }
+    }
+    Ok(texts)
+}
+
+pub fn text_get(path: &str, keyword: &str) -> Result<String, PngError> {
+    text_list(path)?
+        .into_iter()
+        .find(|text| text.keyword() == keyword)
+        .map(|text| text.text().to_string())
+        .ok_or(PngError::KeywordNotFound(keyword.to_string()))
+}
+
+pub fn text_set(path: &str, keyword: &str, text: &str, output: &Output) -> Result<(), PngError> {
     let mut png = open_as_png(path)?;
-    png.remove_chunk(chunk_type)?;
+    text_set_png(&mut png, keyword, text)?;
+    write_output(&png, path, output)
+}
+
+/// Removes every `tEXt` chunk with `keyword`, returning how many were removed.
+pub fn text_delete(path: &str, keyword: &str, output: &Output) -> Result<usize, PngError> {
+    let mut png = open_as_png(path)?;
+    let removed = text_delete_png(&mut png, keyword)?;
+    write_output(&png, path, output)?;
+    Ok(removed)
+}
+
+/// Replaces the first `tEXt` chunk with `keyword` and drops its duplicates.
+/// A new chunk is inserted before `IEND` if there's none yet.
+pub fn text_set_png(png: &mut Png, keyword: &str, text: &str) -> Result<(), PngError> {
+    let new_chunk = TextChunk::new(keyword, text)?.to_chunk();
+
+    let mut replaced = false;
+    let chunks: Vec<Chunk> = png
+        .chunks()
+        .into_iter()
+        .filter_map(|chunk| {
+            if !has_keyword(chunk, keyword) {
+                Some(chunk.clone())
+            } else if replaced {
+                None
+            } else {
+                replaced = true;
+                Some(new_chunk.clone())
+            }
+        })
+        .collect();
+    *png = Png::from_chunks(chunks);
+
+    if !replaced {
+        png.insert_before_iend(new_chunk);
+    }
+    Ok(())
+}
+
+pub fn text_delete_png(png: &mut Png, keyword: &str) -> Result<usize, PngError> {
+    let removed = png.retain(|chunk| !has_keyword(chunk, keyword)).len();
+    if removed == 0 {
+        return Err(PngError::KeywordNotFound(keyword.to_string()));
+    }
+    Ok(removed)
+}
+
+/// Data of `zTXt` and `iTXt` chunks is only readable once parsed, so their text
+/// is returned instead.
+fn decoded_data(chunk: &Chunk) -> Result<String, PngError> {
+    match text_of(chunk) {
+        Some(text) if !TextChunk::is_text_chunk(chunk) => text,
+        _ => Ok(chunk.data_as_string()?),
+    }
+}
+
+/// Text of a chunk found by `decode`, opened with `open_payload` when it was
+/// written by `encode`. Other chunks are read as they are.
+fn decoded_payload(
+    chunk: &Chunk,
+    decryption: Option<&Decryption>,
+    max_decompressed_length: usize,
+) -> Result<String, PngError> {
+    let is_sealed = Payload::is_payload(chunk.data()) || crypto::is_encrypted(chunk.data());
+    if decryption.is_none() && !is_sealed {
+        return decoded_data(chunk);
+    }
+    open_payload(chunk.data(), decryption, max_decompressed_length)?.into_text()
+}
+
+/// Adds the payload header, compresses and encrypts data for `encode` and `hide`.
+fn seal_payload(
+    payload: &Payload,
+    compression: Option<Codec>,
+    encryption: Option<&Encryption>,
+) -> Result<Vec<u8>, PngError> {
+    let data = payload.to_bytes(compression)?;
+    match encryption {
+        Some(encryption) => crypto::seal(&data, encryption),
+        None => Ok(data),
+    }
+}
+
+/// Undoes `seal_payload`. Data without a payload header wasn't written by
+/// `encode` and is returned as a message.
+fn open_payload(
+    data: &[u8],
+    decryption: Option<&Decryption>,
+    max_decompressed_length: usize,
+) -> Result<Payload, PngError> {
+    let data = match decryption {
+        Some(decryption) => crypto::open(data, decryption)?,
+        None if crypto::is_encrypted(data) => return Err(PngError::EncryptedPayload),
+        None => data.to_vec(),
+    };
+    if Payload::is_payload(&data) {
+        return Payload::from_bytes(&data, max_decompressed_length);
+    }
+    Ok(Payload::Message(data))
+}
+
+fn has_keyword(chunk: &Chunk, keyword: &str) -> bool {
+    TextChunk::try_from(chunk).is_ok_and(|text| text.keyword() == keyword)
+}
+
+fn open_as_png(path: &str) -> Result<Png, PngError> {
+    let chunks = open_chunk_reader(path)?
+        .collect::<Result<_, _>>()
+        .map_err(|e| with_path(e, path))?;
+    Ok(Png::from_chunks(chunks))
+}
+
+fn write_output(png: &Png, input_path: &str, output: &Output) -> Result<(), PngError> {
+    match output {
+        Output::Path(path) => write_png(png, path, true),
+        Output::NewPath(path) => write_png(png, path, false),
+        Output::InPlace if input_path == STDIN_PATH => Err(PngError::InvalidOutput(String::from(
+            "stdin can't be modified in place",
+        ))),
+        Output::InPlace => write_png_atomic(png, Path::new(input_path)),
+        Output::Stdout => png
+            .write_to(&mut BufWriter::new(io::stdout().lock()))
+            .map_err(|e| with_path(e, "<stdout>")),
+    }
+}
+
+/// Writes a secret key file readable only by its owner.
+fn write_key_file(key_file: &str, output: &Output) -> Result<(), PngError> {
+    match output {
+        Output::Path(path) | Output::NewPath(path) => {
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            options
+                .open(path)
+                .and_then(|mut file| file.write_all(key_file.as_bytes()))
+                .map_err(|source| PngError::Io {
+                    path: path.display().to_string(),
+                    source,
+                })
+        }
+        Output::Stdout => io::stdout()
+            .write_all(key_file.as_bytes())
+            .map_err(|source| PngError::Io {
+                path: String::from("<stdout>"),
+                source,
+            }),
+        Output::InPlace => Err(PngError::InvalidOutput(String::from(
+            "key file can't be written in place",
+        ))),
+    }
+}
+
+fn write_png(png: &Png, path: &Path, overwrite: bool) -> Result<(), PngError> {
+    let path_str = path.display().to_string();
+    let mut options = OpenOptions::new();
+    match overwrite {
+        true => options.write(true).create(true).truncate(true),
+        false => options.write(true).create_new(true),
+    };
+    let file = options.open(path).map_err(|source| match source.kind() {
+        io::ErrorKind::AlreadyExists => {
+            PngError::InvalidOutput(format!("{} already exists, pick another output", path_str))
+        }
+        _ => PngError::Io {
+            path: path_str.clone(),
+            source,
+        },
+    })?;
+    png.write_to(&mut BufWriter::new(file))
+        .map_err(|e| with_path(e, &path_str))
+}
+
+/// Writes `png` to a temp file next to `path`, syncs it and renames it over `path`,
+/// so readers see either the old or the new file, never a partial one. The
+/// temp file takes the permissions of `path` before the rename.
+fn write_png_atomic(png: &Png, path: &Path) -> Result<(), PngError> {
+    let path_err = |source| PngError::Io {
+        path: path.display().to_string(),
+        source,
+    };
+    let permissions = fs::metadata(path).map_err(path_err)?.permissions();
+    let (tmp_path, file) = create_temp_file(path, random_suffix)?;
+    let tmp_path_str = tmp_path.display().to_string();
+    let io_err = |source| PngError::Io {
+        path: tmp_path_str.clone(),
+        source,
+    };
+
+    let mut writer = BufWriter::new(file);
+    let result = png
+        .write_to(&mut writer)
+        .map_err(|e| with_path(e, &tmp_path_str))
+        .and_then(|_| writer.into_inner().map_err(|e| io_err(e.into_error())))
+        .and_then(|file| {
+            file.set_permissions(permissions).map_err(io_err)?;
+            file.sync_all().map_err(io_err)
+        })
+        .and_then(|_| fs::rename(&tmp_path, path).map_err(path_err));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+        return result;
+    }
+    sync_parent_dir(path)
+}
+
+fn random_suffix() -> Result<u64, PngError> {
+    let mut suffix = [0; 8];
+    crypto::fill_random(&mut suffix)?;
+    Ok(u64::from_be_bytes(suffix))
+}
+
+/// Creates an unused `.{name}.{suffix}.tmp` file next to `path`, drawing a new
+/// suffix after each name that is taken.
+fn create_temp_file(
+    path: &Path,
+    mut suffix: impl FnMut() -> Result<u64, PngError>,
+) -> Result<(PathBuf, File), PngError> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    for _ in 0..TEMP_FILE_ATTEMPTS {
+        let tmp_path = path.with_file_name(format!(".{}.{:016x}.tmp", file_name, suffix()?));
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp_path)
+        {
+            Ok(file) => return Ok((tmp_path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(source) => {
+                return Err(PngError::Io {
+                    path: tmp_path.display().to_string(),
+                    source,
+                })
+            }
+        }
+    }
+    Err(PngError::Io {
+        path: path.display().to_string(),
+        source: io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "no unused temp file name found",
+        ),
+    })
+}
+
+/// Makes a rename inside the directory of `path` durable.
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> Result<(), PngError> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .map_err(|source| PngError::Io {
+            path: dir.display().to_string(),
+            source,
+        })
+}
+
+/// Directories can't be opened for syncing outside of unix.
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> Result<(), PngError> {
+    Ok(())
+}
 
-    let p = Path::new(path);
-    fs::write(p.with_file_name(format!("removed_chunk_{}.png", chunk_type)), png.as_bytes())
-        .map_err(|e| format!("write to file {}: {}", path, e))
+/// Streams chunks from `path` and stops at the first `chunk_type` chunk.
+fn find_chunk(path: &str, chunk_type: &str) -> Result<Chunk, PngError> {
+    let chunks = open_chunk_reader(path)?.map(|chunk| chunk.map_err(|e| with_path(e, path)));
+    collect_payload(chunks, chunk_type)
 }
 
-fn open_as_png(path: &str) -> Result<Png, String> {
-    let png_data = fs::read(path).map_err(|e| format!("open file {}: {}", path, e))?;
-    let png = Png::try_from(png_data.as_slice())?;
-    Ok(png)
+/// Returns the first `chunk_type` chunk. If it holds a fragment, the rest of
+/// its payload is gathered from later chunks and the reassembled data is
+/// returned as a single chunk.
+///
+/// Unsplit data written by `encode` starts with a payload header or an
+/// encryption envelope, so it is never taken for a fragment.
+fn collect_payload(
+    chunks: impl Iterator<Item = Result<Chunk, PngError>>,
+    chunk_type: &str,
+) -> Result<Chunk, PngError> {
+    let given_chunk_type = ChunkType::from_str(chunk_type)?;
+
+    let mut fragments: Vec<Fragment> = Vec::new();
+    for chunk in chunks {
+        let chunk = chunk?;
+        if chunk.chunk_type() != &given_chunk_type {
+            continue;
+        }
+        if !Fragment::is_fragment(chunk.data()) {
+            if fragments.is_empty() {
+                return Ok(chunk);
+            }
+            continue;
+        }
+
+        let fragment = Fragment::try_from(chunk.data())?;
+        // Fragments of other payloads stored under the same type are skipped.
+        if fragments
+            .first()
+            .is_some_and(|first| first.payload_id != fragment.payload_id)
+        {
+            continue;
+        }
+        let total = fragment.total as usize;
+        fragments.push(fragment);
+        if fragments.len() == total {
+            break;
+        }
+    }
+
+    if fragments.is_empty() {
+        return Err(PngError::ChunkNotFound(chunk_type.to_string()));
+    }
+    Ok(Chunk::new(given_chunk_type, reassemble(fragments)?))
+}
+
+/// Opens `path` for streaming, where `-` means stdin.
+fn open_chunk_reader(path: &str) -> Result<ChunkReader<Box<dyn Read>>, PngError> {
+    let input: Box<dyn Read> = match path {
+        STDIN_PATH => Box::new(io::stdin().lock()),
+        path => {
+            let file = File::open(path).map_err(|source| PngError::Io {
+                path: path.to_string(),
+                source,
+            })?;
+            Box::new(BufReader::new(file))
+        }
+    };
+    ChunkReader::new(input).map_err(|e| with_path(e, path))
+}
+
+/// Attaches `path` to i/o errors coming from a reader that doesn't know its file name.
+fn with_path(err: PngError, path: &str) -> PngError {
+    match err {
+        PngError::Io { source, .. } => PngError::Io {
+            path: path.to_string(),
+            source,
+        },
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::{DEFAULT_MAX_DECOMPRESSED_LENGTH, FRAGMENT_HEADER_LENGTH};
+    use crate::signature::ScopeKind;
+    use crate::text::{CompressedTextChunk, InternationalTextChunk};
+
+    fn testing_png() -> Png {
+        let chunk_type = ChunkType::from_str("IEND").unwrap();
+        Png::from_chunks(vec![Chunk::new(chunk_type, Vec::new())])
+    }
+
+    fn message(text: &str) -> Payload {
+        Payload::Message(text.as_bytes().to_vec())
+    }
+
+    /// Scratch directory that is removed on drop, even when a test fails.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path = std::env::temp_dir().join(format!("pngme-{}-{}", name, std::process::id()));
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn path(&self) -> &Path {
+            &self.0
+        }
+
+        /// Writes `png` to `name` inside the directory and returns its path.
+        fn write_png(&self, name: &str, png: &Png) -> String {
+            let path = self.0.join(name);
+            png.write_to(&mut File::create(&path).unwrap()).unwrap();
+            path.to_str().unwrap().to_string()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_encode_decode_png() {
+        let mut png = testing_png();
+        encode_png(
+            &mut png,
+            "ruSt",
+            b"hidden message",
+            ChunkPosition::BeforeIend,
+        )
+        .unwrap();
+        assert_eq!(decode_png(&png, "ruSt").unwrap(), "hidden message");
+        assert_eq!(png.chunks()[1].chunk_type().to_string(), "IEND");
+    }
+
+    #[test]
+    fn test_output_from_arg() {
+        assert_eq!(Output::from_arg("-"), Output::Stdout);
+        assert_eq!(
+            Output::from_arg("out.png"),
+            Output::Path(PathBuf::from("out.png"))
+        );
+    }
+
+    #[test]
+    fn test_encode_in_place() {
+        let dir = TempDir::new("in-place");
+        let path = dir.write_png("image.png", &testing_png());
+
+        encode(
+            &path,
+            "ruSt",
+            &message("hidden message"),
+            &PayloadOptions::default(),
+            &Output::InPlace,
+        )
+        .unwrap();
+
+        assert_eq!(
+            decode(&path, "ruSt", None, DEFAULT_MAX_DECOMPRESSED_LENGTH).unwrap(),
+            "hidden message"
+        );
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_in_place_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new("permissions");
+        let path = dir.write_png("image.png", &testing_png());
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        encode(
+            &path,
+            "ruSt",
+            &message("hidden message"),
+            &PayloadOptions::default(),
+            &Output::InPlace,
+        )
+        .unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_temp_file_collision() {
+        let dir = TempDir::new("collision");
+        let path = dir.path().join("image.png");
+        let stale = dir.path().join(".image.png.0000000000000000.tmp");
+        fs::write(&stale, b"stale").unwrap();
+
+        let mut suffixes = [0, 0, 1].into_iter();
+        let (tmp_path, _) = create_temp_file(&path, || Ok(suffixes.next().unwrap())).unwrap();
+        assert_eq!(tmp_path, dir.path().join(".image.png.0000000000000001.tmp"));
+        assert_eq!(fs::read(&stale).unwrap(), b"stale");
+
+        assert!(matches!(
+            create_temp_file(&path, || Ok(0)),
+            Err(PngError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn test_encode_encrypted() {
+        let mut png = testing_png();
+        encode_payload_png(
+            &mut png,
+            "ruSt",
+            &message("hidden message"),
+            &PayloadOptions {
+                encryption: Some(Encryption::Passphrase(String::from("hunter2"))),
+                ..PayloadOptions::default()
+            },
+        )
+        .unwrap();
+
+        let decode = |passphrase: Option<&str>| {
+            let decryption = passphrase.map(|p| Decryption::Passphrase(p.to_string()));
+            decode_payload_png(
+                &png,
+                "ruSt",
+                decryption.as_ref(),
+                DEFAULT_MAX_DECOMPRESSED_LENGTH,
+            )
+        };
+        assert!(matches!(decode(None), Err(PngError::EncryptedPayload)));
+        assert!(matches!(
+            decode(Some("hunter3")),
+            Err(PngError::AuthenticationFailed)
+        ));
+        assert_eq!(decode(Some("hunter2")).unwrap(), "hidden message");
+    }
+
+    #[test]
+    fn test_keygen_and_recipient_encryption() {
+        let dir = TempDir::new("keygen");
+        let key_path = dir.path().join("key.txt");
+
+        let recipient = keygen(&Output::Path(key_path.clone())).unwrap();
+        assert!(keygen(&Output::Path(key_path.clone())).is_err());
+        #[cfg(unix)]
+        assert_eq!(
+            std::os::unix::fs::PermissionsExt::mode(
+                &fs::metadata(&key_path).unwrap().permissions()
+            ) & 0o777,
+            0o600
+        );
+
+        let mut png = testing_png();
+        encode_payload_png(
+            &mut png,
+            "ruSt",
+            &message("hidden message"),
+            &PayloadOptions {
+                encryption: Some(Encryption::Recipients(vec![recipient])),
+                ..PayloadOptions::default()
+            },
+        )
+        .unwrap();
+
+        let decode = |identity| {
+            decode_payload_png(
+                &png,
+                "ruSt",
+                Some(&Decryption::Identity(identity)),
+                DEFAULT_MAX_DECOMPRESSED_LENGTH,
+            )
+        };
+        let identity = read_identity(key_path.to_str().unwrap()).unwrap();
+        assert_eq!(decode(identity).unwrap(), "hidden message");
+        assert!(matches!(
+            decode(Identity::generate().unwrap()),
+            Err(PngError::AuthenticationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_encode_and_extract_file() {
+        let dir = TempDir::new("extract");
+        let out_dir = dir.path().join("out");
+        fs::create_dir(&out_dir).unwrap();
+        let path = dir.write_png("image.png", &testing_png());
+        let file_path = dir.path().join("secret.zip");
+        let contents: Vec<u8> = (0..=255).collect();
+        fs::write(&file_path, &contents).unwrap();
+
+        let payload = read_payload_file(file_path.to_str().unwrap(), false).unwrap();
+        encode(
+            &path,
+            "ruSt",
+            &payload,
+            &PayloadOptions::default(),
+            &Output::InPlace,
+        )
+        .unwrap();
+
+        let (written, envelope) = extract(
+            &path,
+            "ruSt",
+            None,
+            DEFAULT_MAX_DECOMPRESSED_LENGTH,
+            out_dir.to_str().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(PathBuf::from(written), out_dir.join("secret.zip"));
+        assert_eq!(envelope.mime_type.as_deref(), Some("application/zip"));
+        assert_eq!(fs::read(out_dir.join("secret.zip")).unwrap(), contents);
+
+        let raw = read_payload_file(file_path.to_str().unwrap(), true).unwrap();
+        assert_eq!(raw, Payload::Message(contents.clone()));
+        encode(
+            &path,
+            "raWd",
+            &raw,
+            &PayloadOptions::default(),
+            &Output::InPlace,
+        )
+        .unwrap();
+        assert!(matches!(
+            extract(
+                &path,
+                "raWd",
+                None,
+                DEFAULT_MAX_DECOMPRESSED_LENGTH,
+                out_dir.to_str().unwrap()
+            ),
+            Err(PngError::InvalidOutput(_))
+        ));
+        let raw_out = dir.path().join("raw.bin");
+        let (_, envelope) = extract(
+            &path,
+            "raWd",
+            None,
+            DEFAULT_MAX_DECOMPRESSED_LENGTH,
+            raw_out.to_str().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(envelope.file_name, None);
+        assert_eq!(fs::read(raw_out).unwrap(), contents);
+    }
+
+    #[test]
+    fn test_decode_file_envelope() {
+        let text = FileEnvelope {
+            file_name: Some(String::from("notes.txt")),
+            mime_type: Some(String::from("text/plain")),
+            data: b"plain notes".to_vec(),
+        };
+        let binary = FileEnvelope {
+            file_name: Some(String::from("keys.zip")),
+            mime_type: None,
+            data: vec![0xff, 0x00],
+        };
+        let mut png = testing_png();
+        for (chunk_type, envelope) in [("teXt", text), ("biNa", binary)] {
+            let payload = Payload::File(envelope);
+            encode_payload_png(&mut png, chunk_type, &payload, &PayloadOptions::default()).unwrap();
+        }
+
+        assert_eq!(
+            decode_payload_png(&png, "teXt", None, DEFAULT_MAX_DECOMPRESSED_LENGTH).unwrap(),
+            "plain notes"
+        );
+        assert!(matches!(
+            decode_payload_png(&png, "biNa", None, DEFAULT_MAX_DECOMPRESSED_LENGTH),
+            Err(PngError::BinaryFile(Some(name))) if name == "keys.zip"
+        ));
+    }
+
+    #[test]
+    fn test_encode_compressed_and_encrypted() {
+        let message = r#"{"message": "This is where your secret message will be!"}"#.repeat(20);
+        let identity = Identity::generate().unwrap();
+
+        let mut png = testing_png();
+        encode_payload_png(
+            &mut png,
+            "ruSt",
+            &Payload::Message(message.clone().into_bytes()),
+            &PayloadOptions {
+                compression: Some(Codec::Brotli),
+                encryption: Some(Encryption::Recipients(vec![identity.recipient()])),
+                ..PayloadOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            decode_payload_png(
+                &png,
+                "ruSt",
+                Some(&Decryption::Identity(identity)),
+                DEFAULT_MAX_DECOMPRESSED_LENGTH
+            )
+            .unwrap(),
+            message
+        );
+
+        let mut png = testing_png();
+        encode_payload_png(
+            &mut png,
+            "ruSt",
+            &Payload::Message(message.clone().into_bytes()),
+            &PayloadOptions {
+                compression: Some(Codec::Zstd),
+                ..PayloadOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(png.chunk_by_type("ruSt").unwrap().length() < message.len() as u32);
+        assert_eq!(
+            decode_payload_png(&png, "ruSt", None, DEFAULT_MAX_DECOMPRESSED_LENGTH).unwrap(),
+            message
+        );
+        assert!(matches!(
+            decode_payload_png(&png, "ruSt", None, 100),
+            Err(PngError::Decompression(_))
+        ));
+    }
+
+    #[test]
+    fn test_hide_and_reveal() {
+        let mut png = Png::try_from(&include_bytes!("../test.png")[..]).unwrap();
+        let chunk_count = png.chunks().len();
+
+        hide_png(
+            &mut png,
+            &message("hidden message"),
+            Some(Codec::Zlib),
+            Some(&Encryption::Passphrase(String::from("hunter2"))),
+        )
+        .unwrap();
+
+        assert_eq!(png.chunks().len(), chunk_count);
+        let passphrase = Decryption::Passphrase(String::from("hunter2"));
+        assert_eq!(
+            reveal_png(&png, Some(&passphrase), DEFAULT_MAX_DECOMPRESSED_LENGTH).unwrap(),
+            message("hidden message")
+        );
+        assert!(matches!(
+            reveal_png(&png, None, DEFAULT_MAX_DECOMPRESSED_LENGTH),
+            Err(PngError::EncryptedPayload)
+        ));
+    }
+
+    #[test]
+    fn test_encode_message_starting_with_magic() {
+        for magic in ["PMEp", "PMEz", "PMEs", "PMEf", "PMEn"] {
+            let text = format!("{}{}", magic, "hello".repeat(10));
+            for (compression, max_chunk_length) in [
+                (None, DEFAULT_MAX_CHUNK_LENGTH),
+                (Some(Codec::Zlib), DEFAULT_MAX_CHUNK_LENGTH),
+                (None, FRAGMENT_HEADER_LENGTH + 8),
+            ] {
+                let mut png = testing_png();
+                let options = PayloadOptions {
+                    compression,
+                    max_chunk_length,
+                    ..PayloadOptions::default()
+                };
+                encode_payload_png(&mut png, "ruSt", &message(&text), &options).unwrap();
+                assert_eq!(
+                    decode_payload_png(&png, "ruSt", None, DEFAULT_MAX_DECOMPRESSED_LENGTH)
+                        .unwrap(),
+                    text
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_fragmented() {
+        let message = "This is where your secret message will be!".repeat(10);
+
+        let mut png = testing_png();
+        encode_payload_png(
+            &mut png,
+            "ruSt",
+            &Payload::Message(message.clone().into_bytes()),
+            &PayloadOptions {
+                max_chunk_length: 100,
+                ..PayloadOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(png.chunks_by_type("ruSt").len(), 6);
+        assert_eq!(
+            decode_payload_png(&png, "ruSt", None, DEFAULT_MAX_DECOMPRESSED_LENGTH).unwrap(),
+            message
+        );
+
+        let third = png.chunks_by_type("ruSt")[2].crc();
+        png.retain(|chunk| chunk.crc() != third);
+        assert!(matches!(
+            decode_payload_png(&png, "ruSt", None, DEFAULT_MAX_DECOMPRESSED_LENGTH),
+            Err(PngError::InvalidPayload(_))
+        ));
+    }
+
+    #[test]
+    fn test_new_path_is_never_overwritten() {
+        let dir = TempDir::new("new-path");
+        let output = Output::NewPath(dir.path().join("encoded.png"));
+
+        write_output(&testing_png(), "image.png", &output).unwrap();
+        assert!(matches!(
+            write_output(&testing_png(), "image.png", &output),
+            Err(PngError::InvalidOutput(_))
+        ));
+    }
+
+    #[test]
+    fn test_stdin_cant_be_modified_in_place() {
+        let result = write_output(&testing_png(), STDIN_PATH, &Output::InPlace);
+        assert!(matches!(result, Err(PngError::InvalidOutput(_))));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate("ruSt").is_ok());
+        assert!(matches!(
+            validate("rust"),
+            Err(PngError::InvalidChunkType(_))
+        ));
+    }
+
+    #[test]
+    fn test_describe_chunk_type() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        assert_eq!(
+            describe_chunk_type(&chunk_type),
+            "critical: no (ancillary)\npublic: no (private)\nreserved bit: valid\nsafe to copy: yes"
+        );
+
+        let chunk_type = ChunkType::from_str("IHdR").unwrap();
+        assert_eq!(
+            describe_chunk_type(&chunk_type),
+            "critical: yes\npublic: yes\nreserved bit: invalid\nsafe to copy: no"
+        );
+    }
+
+    #[test]
+    fn test_chunk_info() {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hi".to_vec());
+
+        let info = ChunkInfo::new(5, 1000, &chunk, Some(DataEncoding::Utf8));
+        assert_eq!(
+            serde_json::to_string(&info).unwrap(),
+            format!(
+                "{{\"index\":5,\"offset\":1000,\"type\":\"ruSt\",\"length\":2,\"crc\":{},\
+                \"critical\":false,\"public\":false,\"reserved_bit_valid\":true,\
+                \"safe_to_copy\":true,\"data_encoding\":\"utf8\",\"data\":\"hi\"}}",
+                chunk.crc()
+            )
+        );
+
+        let info = ChunkInfo::new(0, 8, &chunk, None);
+        assert!(!serde_json::to_string(&info).unwrap().contains("data"));
+    }
+
+    #[test]
+    fn test_chunk_info_data_encodings() {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![0xff, 0x00, 0x61]);
+
+        let info = ChunkInfo::new(0, 8, &chunk, Some(DataEncoding::Utf8));
+        assert_eq!(info.data_encoding, Some(DataEncoding::Hex));
+        assert_eq!(info.data.as_deref(), Some("ff0061"));
+
+        let info = ChunkInfo::new(0, 8, &chunk, Some(DataEncoding::Base64));
+        assert_eq!(info.data.as_deref(), Some("/wBh"));
+    }
+
+    #[test]
+    fn test_format_chunk_infos() {
+        let png = Png::try_from(include_bytes!("../test.png").as_ref()).unwrap();
+        let mut offset = 8;
+        let infos: Vec<ChunkInfo> = png
+            .chunks()
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                let info = ChunkInfo::new(index, offset, chunk, None);
+                offset += chunk.length() as usize + 12;
+                info
+            })
+            .collect();
+
+        let jsonl = format_chunk_infos(&infos, ListFormat::Jsonl);
+        assert_eq!(jsonl.lines().count(), 6);
+        assert!(jsonl.lines().nth(4).unwrap().contains("\"offset\":83"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&format_chunk_infos(&infos, ListFormat::Json)).unwrap();
+        assert_eq!(json[0]["type"], "IHDR");
+        assert_eq!(json[5]["critical"], true);
+
+        let table = format_chunk_infos(&infos, ListFormat::Table);
+        assert_eq!(table.lines().count(), 7);
+    }
+
+    #[test]
+    fn test_text_set_and_delete_png() {
+        let mut png = testing_png();
+        text_set_png(&mut png, "Title", "first").unwrap();
+        text_set_png(&mut png, "Author", "me").unwrap();
+        text_set_png(&mut png, "Title", "second").unwrap();
+
+        let texts: Vec<TextChunk> = png
+            .chunks()
+            .iter()
+            .filter_map(|chunk| TextChunk::try_from(*chunk).ok())
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                TextChunk::new("Title", "second").unwrap(),
+                TextChunk::new("Author", "me").unwrap()
+            ]
+        );
+        assert_eq!(png.chunks()[2].chunk_type().to_string(), "IEND");
+
+        png.insert_before_iend(TextChunk::new("Title", "dup").unwrap().to_chunk());
+        assert_eq!(text_delete_png(&mut png, "Title").unwrap(), 2);
+        assert!(matches!(
+            text_delete_png(&mut png, "Title"),
+            Err(PngError::KeywordNotFound(_))
+        ));
+        assert_eq!(png.chunks().len(), 2);
+    }
+
+    #[test]
+    fn test_decode_compressed_text() {
+        let mut png = testing_png();
+        png.insert_before_iend(
+            CompressedTextChunk::new("Comment", "squeezed")
+                .unwrap()
+                .to_chunk(),
+        );
+        png.insert_before_iend(
+            InternationalTextChunk::new("Title", "de", "Titel", "Grüße", true)
+                .unwrap()
+                .to_chunk(),
+        );
+
+        assert_eq!(decode_png(&png, "zTXt").unwrap(), "squeezed");
+        assert_eq!(decode_png(&png, "iTXt").unwrap(), "Grüße");
+    }
+
+    #[test]
+    fn test_sign_and_verify_png() {
+        let mut png = testing_png();
+        png.insert_before_iend(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            b"hidden".to_vec(),
+        ));
+        let alice = SigningKey::generate().unwrap();
+        let bob = SigningKey::generate().unwrap();
+        let all = || SignatureScope {
+            kind: ScopeKind::All,
+            include: Vec::new(),
+        };
+
+        assert!(matches!(
+            verify_png(&png, None),
+            Err(PngError::ChunkNotFound(_))
+        ));
+
+        sign_png(&mut png, &alice, all()).unwrap();
+        sign_png(&mut png, &bob, all()).unwrap();
+        sign_png(&mut png, &alice, all()).unwrap();
+        assert_eq!(verify_png(&png, None).unwrap().len(), 2);
+        assert_eq!(
+            verify_png(&png, Some(&alice.verifying_key())).unwrap()[0].key(),
+            &alice.verifying_key()
+        );
+        let stranger = SigningKey::generate().unwrap().verifying_key();
+        assert!(matches!(
+            verify_png(&png, Some(&stranger)),
+            Err(PngError::ChunkNotFound(_))
+        ));
+
+        encode_png(&mut png, "ruSt", b"tampered", ChunkPosition::BeforeIend).unwrap();
+        assert!(matches!(
+            verify_png(&png, Some(&bob.verifying_key())),
+            Err(PngError::SignatureMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_remove_chunks_png() {
+        let mut png = testing_png();
+        for (keyword, text) in [("Author", "a"), ("Title", "b"), ("Author", "a longer text")] {
+            png.insert_before_iend(TextChunk::new(keyword, text).unwrap().to_chunk());
+        }
+        let author = [ChunkFilter::Keyword(String::from("Author"))];
+
+        assert!(matches!(
+            remove_chunks_png(&mut png, "tEXt", Occurrence::Index(2), &author),
+            Err(PngError::ChunkNotFound(_))
+        ));
+        let removed = remove_chunks_png(&mut png, "tEXt", Occurrence::Index(1), &author).unwrap();
+        assert_eq!(removed[0].data(), b"Author\0a longer text");
+
+        let large = [ChunkFilter::LargerThan(7)];
+        let removed = remove_chunks_png(&mut png, "tEXt", Occurrence::All, &large).unwrap();
+        assert_eq!(removed[0].data(), b"Author\0a");
+        let removed = remove_chunks_png(&mut png, "tEXt", Occurrence::First, &[]).unwrap();
+        assert_eq!(removed[0].data(), b"Title\0b");
+        assert!(matches!(
+            remove_chunks_png(&mut png, "tEXt", Occurrence::All, &[]),
+            Err(PngError::ChunkNotFound(_))
+        ));
+        assert_eq!(png.chunks().len(), testing_png().chunks().len());
+    }
+
+    #[test]
+    fn test_chunk_filter_from_str() {
+        assert_eq!(
+            ChunkFilter::from_str("keyword=Author").unwrap(),
+            ChunkFilter::Keyword(String::from("Author"))
+        );
+        assert_eq!(
+            ChunkFilter::from_str("size>1024").unwrap(),
+            ChunkFilter::LargerThan(1024)
+        );
+        assert_eq!(
+            ChunkFilter::from_str("size<10").unwrap(),
+            ChunkFilter::SmallerThan(10)
+        );
+        for invalid in ["size>big", "size=10", "author=Ferris"] {
+            assert!(matches!(
+                ChunkFilter::from_str(invalid),
+                Err(PngError::InvalidFormat(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_remove_chunk_png() {
+        let mut png = testing_png();
+        encode_png(
+            &mut png,
+            "ruSt",
+            b"hidden message",
+            ChunkPosition::BeforeIend,
+        )
+        .unwrap();
+        let removed = remove_chunk_png(&mut png, "ruSt").unwrap();
+        assert_eq!(removed.data(), b"hidden message");
+        assert!(matches!(
+            decode_png(&png, "ruSt"),
+            Err(PngError::ChunkNotFound(_))
+        ));
+    }
 }
@@ -0,0 +1,205 @@
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+    str::FromStr,
+};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::error::PngError;
+
+/// Level zlib itself defaults to.
+pub const DEFAULT_DEFLATE_LEVEL: u32 = 6;
+
+/// Level passed to zstd, its own default.
+const ZSTD_LEVEL: i32 = 3;
+/// Quality and window size passed to brotli, the defaults of its CLI.
+const BROTLI_QUALITY: u32 = 11;
+const BROTLI_WINDOW_BITS: u32 = 22;
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+/// Compression codec of an embedded payload, stored as a byte in its header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Zlib = 1,
+    Zstd = 2,
+    Brotli = 3,
+}
+
+impl TryFrom<u8> for Codec {
+    type Error = PngError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Codec::Zlib),
+            2 => Ok(Codec::Zstd),
+            3 => Ok(Codec::Brotli),
+            other => Err(PngError::Decompression(format!(
+                "unknown compression codec {}",
+                other
+            ))),
+        }
+    }
+}
+
+impl FromStr for Codec {
+    type Err = PngError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zlib" => Ok(Codec::Zlib),
+            "zstd" => Ok(Codec::Zstd),
+            "brotli" => Ok(Codec::Brotli),
+            other => Err(PngError::InvalidFormat(other.to_string())),
+        }
+    }
+}
+
+impl Display for Codec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Codec::Zlib => "zlib",
+            Codec::Zstd => "zstd",
+            Codec::Brotli => "brotli",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Compresses `data` with `codec`.
+pub fn compress(data: &[u8], codec: Codec) -> Vec<u8> {
+    match codec {
+        Codec::Zlib => deflate(data),
+        Codec::Zstd => zstd::encode_all(data, ZSTD_LEVEL).expect("reading from slice never fails"),
+        Codec::Brotli => {
+            let mut encoder = brotli::CompressorWriter::new(
+                Vec::new(),
+                BROTLI_BUFFER_SIZE,
+                BROTLI_QUALITY,
+                BROTLI_WINDOW_BITS,
+            );
+            encoder.write_all(data).expect("writing to Vec never fails");
+            encoder.into_inner()
+        }
+    }
+}
+
+/// Decompresses `data` written by `compress`, failing once the output exceeds
+/// `max_length` bytes.
+pub fn decompress(data: &[u8], codec: Codec, max_length: usize) -> Result<Vec<u8>, PngError> {
+    match codec {
+        Codec::Zlib => inflate(data, max_length),
+        Codec::Zstd => {
+            let decoder =
+                zstd::Decoder::new(data).map_err(|e| PngError::Decompression(e.to_string()))?;
+            read_limited(decoder, max_length)
+        }
+        Codec::Brotli => read_limited(
+            brotli::Decompressor::new(data, BROTLI_BUFFER_SIZE),
+            max_length,
+        ),
+    }
+}
+
+/// Compresses `data` into a zlib stream, the only compression method PNG defines.
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    deflate_with_level(data, DEFAULT_DEFLATE_LEVEL)
+}
+
+/// Like `deflate`, from 0 for no compression to 9 for the smallest output.
+/// Higher levels are treated as 9.
+pub fn deflate_with_level(data: &[u8], level: u32) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level.min(9)));
+    encoder.write_all(data).expect("writing to Vec never fails");
+    encoder.finish().expect("writing to Vec never fails")
+}
+
+/// Decompresses a zlib stream, failing once the output exceeds `max_length` bytes
+/// so a small chunk can't expand into an unbounded allocation.
+pub fn inflate(data: &[u8], max_length: usize) -> Result<Vec<u8>, PngError> {
+    read_limited(ZlibDecoder::new(data), max_length)
+}
+
+/// Reads a decoder to the end, reading at most one byte past `max_length`.
+fn read_limited(decoder: impl Read, max_length: usize) -> Result<Vec<u8>, PngError> {
+    let mut inflated = Vec::new();
+    decoder
+        .take(max_length as u64 + 1)
+        .read_to_end(&mut inflated)
+        .map_err(|e| PngError::Decompression(e.to_string()))?;
+
+    if inflated.len() > max_length {
+        return Err(PngError::Decompression(format!(
+            "data inflates to more than {} bytes",
+            max_length
+        )));
+    }
+    Ok(inflated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deflate_roundtrip() {
+        let data = "This is where your secret message will be!".repeat(10);
+        let deflated = deflate(data.as_bytes());
+
+        assert!(deflated.len() < data.len());
+        assert_eq!(inflate(&deflated, data.len()).unwrap(), data.as_bytes());
+    }
+
+    #[test]
+    fn test_deflate_levels() {
+        let data = "This is where your secret message will be!".repeat(10);
+        let stored = deflate_with_level(data.as_bytes(), 0);
+        let best = deflate_with_level(data.as_bytes(), 42);
+
+        assert!(stored.len() > data.len());
+        assert!(best.len() <= deflate(data.as_bytes()).len());
+        assert_eq!(inflate(&best, data.len()).unwrap(), data.as_bytes());
+    }
+
+    #[test]
+    fn test_inflate_limit() {
+        let deflated = deflate(&[0; 1024]);
+        assert!(matches!(
+            inflate(&deflated, 1023),
+            Err(PngError::Decompression(_))
+        ));
+    }
+
+    #[test]
+    fn test_inflate_garbage() {
+        assert!(inflate(b"not zlib", 1024).is_err());
+    }
+
+    #[test]
+    fn test_codecs_roundtrip() {
+        let data = "This is where your secret message will be!".repeat(10);
+        for codec in [Codec::Zlib, Codec::Zstd, Codec::Brotli] {
+            let compressed = compress(data.as_bytes(), codec);
+
+            assert!(compressed.len() < data.len());
+            assert_eq!(
+                decompress(&compressed, codec, data.len()).unwrap(),
+                data.as_bytes()
+            );
+            assert!(matches!(
+                decompress(&compressed, codec, data.len() - 1),
+                Err(PngError::Decompression(_))
+            ));
+            assert_eq!(Codec::from_str(&codec.to_string()).unwrap(), codec);
+            assert_eq!(Codec::try_from(codec as u8).unwrap(), codec);
+        }
+    }
+
+    #[test]
+    fn test_invalid_codec() {
+        assert!(Codec::from_str("gzip").is_err());
+        assert!(Codec::try_from(0).is_err());
+        assert!(decompress(b"not brotli", Codec::Brotli, 1024).is_err());
+        assert!(decompress(b"not zstd", Codec::Zstd, 1024).is_err());
+    }
+}
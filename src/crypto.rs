@@ -0,0 +1,635 @@
+use std::{fmt::Display, io, str::FromStr};
+
+use argon2::{Argon2, Params};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, SharedSecret, StaticSecret};
+
+use crate::{chunk::read_array, error::PngError};
+
+/// Marks chunk data holding an encrypted envelope.
+const MAGIC: &[u8; 4] = b"PMEn";
+
+/// Version of the envelope layout, bumped on incompatible changes.
+pub const ENVELOPE_VERSION: u8 = 1;
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const KEY_LENGTH: usize = 32;
+/// Payload key encrypted to a single recipient, including the tag.
+const WRAPPED_KEY_LENGTH: usize = KEY_LENGTH + 16;
+
+/// Key derivation parameters above these are rejected when decrypting, so a
+/// crafted envelope can't make us allocate more than 256 MiB or run more than
+/// a few seconds. The defaults we write use 19 MiB and 2 passes.
+const MAX_MEMORY_KIB: u32 = 256 * 1024;
+const MAX_ITERATIONS: u32 = 10;
+const MAX_PARALLELISM: u32 = 16;
+
+/// Key derivation and cipher used for the payload, stored in the envelope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Key derived from a passphrase with Argon2id, payload sealed with ChaCha20-Poly1305.
+    Argon2idChaCha20Poly1305 = 1,
+    /// Random payload key wrapped for every X25519 recipient, payload sealed
+    /// with ChaCha20-Poly1305.
+    X25519ChaCha20Poly1305 = 2,
+}
+
+impl TryFrom<u8> for Algorithm {
+    type Error = PngError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Algorithm::Argon2idChaCha20Poly1305),
+            2 => Ok(Algorithm::X25519ChaCha20Poly1305),
+            other => Err(PngError::InvalidEnvelope(format!(
+                "unknown algorithm {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Argon2id cost parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// Public key a payload can be encrypted to, written as `pngme-pub:<base64>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recipient(PublicKey);
+
+impl Recipient {
+    pub const PREFIX: &'static str = "pngme-pub:";
+}
+
+impl FromStr for Recipient {
+    type Err = PngError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = decode_key(s.trim(), Recipient::PREFIX)?;
+        Ok(Recipient(PublicKey::from(bytes)))
+    }
+}
+
+impl Display for Recipient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            Recipient::PREFIX,
+            BASE64.encode(self.0.as_bytes())
+        )
+    }
+}
+
+/// Secret key able to open payloads encrypted to its `Recipient`.
+///
+/// It's deliberately not `Display` nor `Debug`, so it doesn't end up in logs.
+#[derive(Clone)]
+pub struct Identity(StaticSecret);
+
+impl Identity {
+    pub const PREFIX: &'static str = "PNGME-SECRET-KEY:";
+
+    pub fn generate() -> Result<Identity, PngError> {
+        let mut secret = [0; KEY_LENGTH];
+        fill_random(&mut secret)?;
+        Ok(Identity(StaticSecret::from(secret)))
+    }
+
+    pub fn recipient(&self) -> Recipient {
+        Recipient(PublicKey::from(&self.0))
+    }
+
+    /// Contents of a key file, the public key is included as a comment.
+    pub fn to_key_file(&self) -> String {
+        format!(
+            "# public key: {}\n{}{}\n",
+            self.recipient(),
+            Identity::PREFIX,
+            BASE64.encode(self.0.as_bytes())
+        )
+    }
+}
+
+impl FromStr for Identity {
+    type Err = PngError;
+
+    /// Parses a key file, skipping empty lines and `#` comments.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = decode_key(key_file_line(s)?, Identity::PREFIX)?;
+        Ok(Identity(StaticSecret::from(bytes)))
+    }
+}
+
+/// How a payload gets encrypted.
+pub enum Encryption {
+    Passphrase(String),
+    Recipients(Vec<Recipient>),
+}
+
+/// Secret that opens an encrypted payload.
+pub enum Decryption {
+    Passphrase(String),
+    Identity(Identity),
+}
+
+/// Encrypts `plaintext` into an envelope, see `encrypt` and `encrypt_to_recipients`.
+pub fn seal(plaintext: &[u8], encryption: &Encryption) -> Result<Vec<u8>, PngError> {
+    match encryption {
+        Encryption::Passphrase(passphrase) => encrypt(plaintext, passphrase),
+        Encryption::Recipients(recipients) => encrypt_to_recipients(plaintext, recipients),
+    }
+}
+
+/// Opens an envelope made by `seal`.
+pub fn open(envelope: &[u8], decryption: &Decryption) -> Result<Vec<u8>, PngError> {
+    match decryption {
+        Decryption::Passphrase(passphrase) => decrypt(envelope, passphrase),
+        Decryption::Identity(identity) => decrypt_with_identity(envelope, identity),
+    }
+}
+
+/// Whether `data` starts like an encrypted envelope.
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Encrypts `plaintext` with a key derived from `passphrase` using default
+/// Argon2id parameters.
+pub fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>, PngError> {
+    encrypt_with_params(plaintext, passphrase, KdfParams::default())
+}
+
+/// Encrypts `plaintext` into a versioned envelope:
+/// magic, version, algorithm, Argon2id parameters, salt, nonce and the ciphertext.
+/// Everything before the ciphertext is authenticated along with it.
+pub fn encrypt_with_params(
+    plaintext: &[u8],
+    passphrase: &str,
+    params: KdfParams,
+) -> Result<Vec<u8>, PngError> {
+    let mut salt = [0; SALT_LENGTH];
+    let mut nonce = [0; NONCE_LENGTH];
+    fill_random(&mut salt)?;
+    fill_random(&mut nonce)?;
+
+    let mut envelope: Vec<u8> = MAGIC
+        .iter()
+        .copied()
+        .chain([ENVELOPE_VERSION, Algorithm::Argon2idChaCha20Poly1305 as u8])
+        .chain(params.memory_kib.to_be_bytes())
+        .chain(params.iterations.to_be_bytes())
+        .chain(params.parallelism.to_be_bytes())
+        .chain(salt)
+        .chain(nonce)
+        .collect();
+
+    let key = derive_key(passphrase, &salt, params)?;
+    let ciphertext = ChaCha20Poly1305::new(&key)
+        .encrypt(
+            &Nonce::from(nonce),
+            Payload {
+                msg: plaintext,
+                aad: &envelope,
+            },
+        )
+        .expect("encryption only fails on oversized messages");
+
+    envelope.extend(ciphertext);
+    Ok(envelope)
+}
+
+/// Opens an envelope made by `encrypt`. A wrong passphrase or modified data
+/// fails with `PngError::AuthenticationFailed`.
+pub fn decrypt(envelope: &[u8], passphrase: &str) -> Result<Vec<u8>, PngError> {
+    if parse_header(envelope)? != Algorithm::Argon2idChaCha20Poly1305 {
+        return Err(PngError::InvalidEnvelope(String::from(
+            "data is encrypted to recipients, not with a passphrase",
+        )));
+    }
+
+    let mut offset = MAGIC.len() + 2;
+    let mut next_u32 = || -> Result<u32, PngError> {
+        let bytes = read_array(envelope, offset).map_err(|_| truncated())?;
+        offset += 4;
+        Ok(u32::from_be_bytes(bytes))
+    };
+    let params = KdfParams {
+        memory_kib: next_u32()?,
+        iterations: next_u32()?,
+        parallelism: next_u32()?,
+    };
+    if params.memory_kib > MAX_MEMORY_KIB
+        || params.iterations > MAX_ITERATIONS
+        || params.parallelism > MAX_PARALLELISM
+    {
+        return Err(PngError::InvalidEnvelope(format!(
+            "key derivation parameters are too expensive: {:?}",
+            params
+        )));
+    }
+
+    let salt: [u8; SALT_LENGTH] = read_array(envelope, offset).map_err(|_| truncated())?;
+    let nonce: [u8; NONCE_LENGTH] =
+        read_array(envelope, offset + SALT_LENGTH).map_err(|_| truncated())?;
+    let (header, ciphertext) = envelope.split_at(offset + SALT_LENGTH + NONCE_LENGTH);
+
+    let key = derive_key(passphrase, &salt, params)?;
+    ChaCha20Poly1305::new(&key)
+        .decrypt(
+            &Nonce::from(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| PngError::AuthenticationFailed)
+}
+
+/// Encrypts `plaintext` to every recipient. A random payload key is wrapped
+/// for each of them with a key agreed between an ephemeral X25519 key and the
+/// recipient, so any one of their identities opens the envelope.
+///
+/// The envelope holds magic, version, algorithm, ephemeral public key,
+/// recipient count, wrapped keys, nonce and the ciphertext.
+pub fn encrypt_to_recipients(
+    plaintext: &[u8],
+    recipients: &[Recipient],
+) -> Result<Vec<u8>, PngError> {
+    if recipients.is_empty() || recipients.len() > u8::MAX as usize {
+        return Err(PngError::InvalidKey(format!(
+            "1 to {} recipients are required, got {}",
+            u8::MAX,
+            recipients.len()
+        )));
+    }
+
+    let ephemeral = Identity::generate()?;
+    let ephemeral_public = ephemeral.recipient();
+    let mut payload_key = [0; KEY_LENGTH];
+    let mut nonce = [0; NONCE_LENGTH];
+    fill_random(&mut payload_key)?;
+    fill_random(&mut nonce)?;
+
+    let mut envelope: Vec<u8> = MAGIC
+        .iter()
+        .copied()
+        .chain([ENVELOPE_VERSION, Algorithm::X25519ChaCha20Poly1305 as u8])
+        .chain(ephemeral_public.0.to_bytes())
+        .chain([recipients.len() as u8])
+        .collect();
+
+    for recipient in recipients {
+        let shared = ephemeral.0.diffie_hellman(&recipient.0);
+        let wrapping_key = wrapping_key(shared, &ephemeral_public, recipient)?;
+        let wrapped = ChaCha20Poly1305::new(&wrapping_key)
+            .encrypt(&Nonce::default(), &payload_key[..])
+            .expect("encryption only fails on oversized messages");
+        envelope.extend(wrapped);
+    }
+    envelope.extend(nonce);
+
+    let ciphertext = ChaCha20Poly1305::new(&Key::from(payload_key))
+        .encrypt(
+            &Nonce::from(nonce),
+            Payload {
+                msg: plaintext,
+                aad: &envelope,
+            },
+        )
+        .expect("encryption only fails on oversized messages");
+
+    envelope.extend(ciphertext);
+    Ok(envelope)
+}
+
+/// Opens an envelope made by `encrypt_to_recipients`. Fails with
+/// `PngError::AuthenticationFailed` when it wasn't encrypted to `identity`.
+pub fn decrypt_with_identity(envelope: &[u8], identity: &Identity) -> Result<Vec<u8>, PngError> {
+    if parse_header(envelope)? != Algorithm::X25519ChaCha20Poly1305 {
+        return Err(PngError::InvalidEnvelope(String::from(
+            "data is encrypted with a passphrase, not to recipients",
+        )));
+    }
+
+    let offset = MAGIC.len() + 2;
+    let ephemeral_public = Recipient(PublicKey::from(
+        read_array::<KEY_LENGTH>(envelope, offset).map_err(|_| truncated())?,
+    ));
+    let count = *envelope.get(offset + KEY_LENGTH).ok_or_else(truncated)? as usize;
+    let wrapped_keys_start = offset + KEY_LENGTH + 1;
+    let nonce_start = wrapped_keys_start + count * WRAPPED_KEY_LENGTH;
+    let nonce: [u8; NONCE_LENGTH] = read_array(envelope, nonce_start).map_err(|_| truncated())?;
+    let (header, ciphertext) = envelope.split_at(nonce_start + NONCE_LENGTH);
+
+    let shared = identity.0.diffie_hellman(&ephemeral_public.0);
+    let wrapping_key = wrapping_key(shared, &ephemeral_public, &identity.recipient())?;
+    let payload_key = header[wrapped_keys_start..nonce_start]
+        .chunks_exact(WRAPPED_KEY_LENGTH)
+        .find_map(|wrapped| {
+            ChaCha20Poly1305::new(&wrapping_key)
+                .decrypt(&Nonce::default(), wrapped)
+                .ok()
+        })
+        .ok_or(PngError::AuthenticationFailed)?;
+
+    ChaCha20Poly1305::new_from_slice(&payload_key)
+        .map_err(|_| PngError::AuthenticationFailed)?
+        .decrypt(
+            &Nonce::from(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| PngError::AuthenticationFailed)
+}
+
+/// Checks magic and version, returning the algorithm of the envelope.
+fn parse_header(envelope: &[u8]) -> Result<Algorithm, PngError> {
+    if !is_encrypted(envelope) {
+        return Err(PngError::InvalidEnvelope(String::from(
+            "data is not encrypted",
+        )));
+    }
+    let version = *envelope.get(MAGIC.len()).ok_or_else(truncated)?;
+    if version != ENVELOPE_VERSION {
+        return Err(PngError::InvalidEnvelope(format!(
+            "unsupported version {}",
+            version
+        )));
+    }
+    let algorithm = *envelope.get(MAGIC.len() + 1).ok_or_else(truncated)?;
+    Algorithm::try_from(algorithm)
+}
+
+/// Derives the key wrapping the payload key for `recipient`. Both public keys
+/// are mixed in, so the key is unique per envelope and recipient, which makes
+/// the all-zero nonce safe.
+fn wrapping_key(
+    shared: SharedSecret,
+    ephemeral_public: &Recipient,
+    recipient: &Recipient,
+) -> Result<Key, PngError> {
+    if !shared.was_contributory() {
+        return Err(PngError::InvalidKey(String::from(
+            "key agreement produced an all-zero secret",
+        )));
+    }
+
+    let salt: Vec<u8> = ephemeral_public
+        .0
+        .as_bytes()
+        .iter()
+        .chain(recipient.0.as_bytes())
+        .copied()
+        .collect();
+    let mut key = [0; KEY_LENGTH];
+    Hkdf::<Sha256>::new(Some(&salt), shared.as_bytes())
+        .expand(b"pngme x25519 payload key", &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    Ok(Key::from(key))
+}
+
+fn truncated() -> PngError {
+    PngError::InvalidEnvelope(String::from("envelope is truncated"))
+}
+
+/// First line of a key file that isn't empty or a `#` comment.
+pub(crate) fn key_file_line(key_file: &str) -> Result<&str, PngError> {
+    key_file
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .ok_or(PngError::InvalidKey(String::from("key file has no key")))
+}
+
+pub(crate) fn decode_key(s: &str, prefix: &str) -> Result<[u8; KEY_LENGTH], PngError> {
+    let invalid = || PngError::InvalidKey(format!("expected {}<base64 key>", prefix));
+
+    let encoded = s.strip_prefix(prefix).ok_or_else(invalid)?;
+    BASE64
+        .decode(encoded)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(invalid)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], params: KdfParams) -> Result<Key, PngError> {
+    let invalid_params =
+        |e: argon2::Error| PngError::InvalidEnvelope(format!("invalid key derivation: {}", e));
+
+    let params = Params::new(
+        params.memory_kib,
+        params.iterations,
+        params.parallelism,
+        Some(32),
+    )
+    .map_err(invalid_params)?;
+
+    let mut key = [0; 32];
+    Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(invalid_params)?;
+    Ok(Key::from(key))
+}
+
+pub(crate) fn fill_random(buf: &mut [u8]) -> Result<(), PngError> {
+    getrandom::fill(buf).map_err(|e| PngError::Io {
+        path: String::from("<random>"),
+        source: io::Error::other(e.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cheap parameters, the defaults are slow in unoptimized builds.
+    const TEST_PARAMS: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn test_encrypt_roundtrip() {
+        let envelope = encrypt_with_params(b"secret", "hunter2", TEST_PARAMS).unwrap();
+
+        assert!(is_encrypted(&envelope));
+        assert!(!envelope.windows(6).any(|window| window == b"secret"));
+        assert_eq!(decrypt(&envelope, "hunter2").unwrap(), b"secret");
+    }
+
+    #[test]
+    fn test_random_salt_and_nonce() {
+        let first = encrypt_with_params(b"secret", "hunter2", TEST_PARAMS).unwrap();
+        let second = encrypt_with_params(b"secret", "hunter2", TEST_PARAMS).unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_wrong_passphrase() {
+        let envelope = encrypt_with_params(b"secret", "hunter2", TEST_PARAMS).unwrap();
+        assert!(matches!(
+            decrypt(&envelope, "hunter3"),
+            Err(PngError::AuthenticationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_tampered_envelope() {
+        let envelope = encrypt_with_params(b"secret", "hunter2", TEST_PARAMS).unwrap();
+
+        let mut tampered = envelope.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            decrypt(&tampered, "hunter2"),
+            Err(PngError::AuthenticationFailed)
+        ));
+
+        // Header is authenticated too, bumping iterations must not go unnoticed.
+        let mut tampered = envelope.clone();
+        tampered[13] += 1;
+        assert!(matches!(
+            decrypt(&tampered, "hunter2"),
+            Err(PngError::AuthenticationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_recipients_roundtrip() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let envelope =
+            encrypt_to_recipients(b"secret", &[alice.recipient(), bob.recipient()]).unwrap();
+
+        assert!(is_encrypted(&envelope));
+        assert_eq!(decrypt_with_identity(&envelope, &alice).unwrap(), b"secret");
+        assert_eq!(decrypt_with_identity(&envelope, &bob).unwrap(), b"secret");
+
+        let eve = Identity::generate().unwrap();
+        assert!(matches!(
+            decrypt_with_identity(&envelope, &eve),
+            Err(PngError::AuthenticationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_recipients_tampered_envelope() {
+        let alice = Identity::generate().unwrap();
+        let envelope = encrypt_to_recipients(b"secret", &[alice.recipient()]).unwrap();
+
+        for index in [10, 50, envelope.len() - 1] {
+            let mut tampered = envelope.clone();
+            tampered[index] ^= 1;
+            assert!(matches!(
+                decrypt_with_identity(&tampered, &alice),
+                Err(PngError::AuthenticationFailed)
+            ));
+        }
+
+        let mut more_recipients = envelope.clone();
+        more_recipients[38] = 2;
+        assert!(matches!(
+            decrypt_with_identity(&more_recipients, &alice),
+            Err(PngError::InvalidEnvelope(_))
+        ));
+    }
+
+    #[test]
+    fn test_mismatched_decryption() {
+        let alice = Identity::generate().unwrap();
+        let to_alice = encrypt_to_recipients(b"secret", &[alice.recipient()]).unwrap();
+        let with_passphrase = encrypt_with_params(b"secret", "hunter2", TEST_PARAMS).unwrap();
+
+        assert!(matches!(
+            decrypt(&to_alice, "hunter2"),
+            Err(PngError::InvalidEnvelope(_))
+        ));
+        assert!(matches!(
+            decrypt_with_identity(&with_passphrase, &alice),
+            Err(PngError::InvalidEnvelope(_))
+        ));
+        assert!(encrypt_to_recipients(b"secret", &[]).is_err());
+    }
+
+    #[test]
+    fn test_key_encoding() {
+        let identity = Identity::generate().unwrap();
+        let key_file = identity.to_key_file();
+        let parsed = Identity::from_str(&key_file).unwrap();
+        assert_eq!(parsed.recipient(), identity.recipient());
+
+        let recipient = identity.recipient().to_string();
+        assert!(recipient.starts_with(Recipient::PREFIX));
+        assert!(key_file.contains(&recipient));
+        assert_eq!(
+            Recipient::from_str(&recipient).unwrap(),
+            identity.recipient()
+        );
+
+        for invalid in ["", "pngme-pub:", "pngme-pub:AAAA", "age1abc"] {
+            assert!(matches!(
+                Recipient::from_str(invalid),
+                Err(PngError::InvalidKey(_))
+            ));
+        }
+        assert!(Identity::from_str("# only a comment\n").is_err());
+        assert!(Identity::from_str(&recipient).is_err());
+    }
+
+    #[test]
+    fn test_invalid_envelope() {
+        let envelope = encrypt_with_params(b"secret", "hunter2", TEST_PARAMS).unwrap();
+
+        let mut wrong_version = envelope.clone();
+        wrong_version[4] = 2;
+        let mut wrong_algorithm = envelope.clone();
+        wrong_algorithm[5] = 0;
+        let mut too_expensive = envelope.clone();
+        too_expensive[6] = 0xff;
+        let mut too_much_memory = envelope.clone();
+        too_much_memory[6..10].copy_from_slice(&(MAX_MEMORY_KIB + 1).to_be_bytes());
+        let mut too_many_iterations = envelope.clone();
+        too_many_iterations[10..14].copy_from_slice(&(MAX_ITERATIONS + 1).to_be_bytes());
+
+        for data in [
+            &b"plain"[..],
+            &envelope[..20],
+            &envelope[..40],
+            &wrong_version,
+            &wrong_algorithm,
+            &too_expensive,
+            &too_much_memory,
+            &too_many_iterations,
+        ] {
+            assert!(
+                matches!(decrypt(data, "hunter2"), Err(PngError::InvalidEnvelope(_))),
+                "{:?} should be invalid",
+                data
+            );
+        }
+    }
+}
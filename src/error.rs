@@ -0,0 +1,224 @@
+use std::{error::Error, fmt::Display, io, string::FromUtf8Error};
+
+use crate::chunk_type::ChunkType;
+
+#[derive(Debug)]
+pub enum PngError {
+    InvalidSignature,
+    CrcMismatch {
+        chunk_type: ChunkType,
+        expected: u32,
+        actual: u32,
+        offset: usize,
+    },
+    Truncated {
+        offset: usize,
+    },
+    TrailingData {
+        offset: usize,
+    },
+    ChunkTooLarge {
+        length: u32,
+        max: u32,
+        offset: usize,
+    },
+    InvalidChunkType(String),
+    ChunkNotFound(String),
+    IndexOutOfRange {
+        index: usize,
+        len: usize,
+    },
+    InvalidPosition(String),
+    InvalidOutput(String),
+    InvalidFormat(String),
+    InvalidIhdr(String),
+    StructureViolations(usize),
+    InvalidText(String),
+    KeywordNotFound(String),
+    Decompression(String),
+    InvalidEnvelope(String),
+    AuthenticationFailed,
+    InvalidKey(String),
+    InvalidSignatureChunk(String),
+    SignatureMismatch,
+    InvalidPayload(String),
+    UnsupportedImage(String),
+    InvalidImageData(String),
+    CapacityExceeded {
+        needed: usize,
+        capacity: usize,
+    },
+    /// Payload is an embedded file that isn't text, with its name when known.
+    BinaryFile(Option<String>),
+    /// Payload is encrypted and no way to decrypt it was given.
+    EncryptedPayload,
+    InvalidUtf8(FromUtf8Error),
+    Io {
+        path: String,
+        source: io::Error,
+    },
+}
+
+impl PngError {
+    /// Shifts offsets of a chunk-level error by `base`, so they point into the whole file.
+    pub fn at_offset(self, base: usize) -> PngError {
+        match self {
+            PngError::CrcMismatch {
+                chunk_type,
+                expected,
+                actual,
+                offset,
+            } => PngError::CrcMismatch {
+                chunk_type,
+                expected,
+                actual,
+                offset: offset + base,
+            },
+            PngError::Truncated { offset } => PngError::Truncated {
+                offset: offset + base,
+            },
+            PngError::TrailingData { offset } => PngError::TrailingData {
+                offset: offset + base,
+            },
+            PngError::ChunkTooLarge {
+                length,
+                max,
+                offset,
+            } => PngError::ChunkTooLarge {
+                length,
+                max,
+                offset: offset + base,
+            },
+            other => other,
+        }
+    }
+}
+
+impl Display for PngError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PngError::InvalidSignature => write!(f, "invalid png signature"),
+            PngError::CrcMismatch {
+                chunk_type,
+                expected,
+                actual,
+                offset,
+            } => write!(
+                f,
+                "corrupted crc of {} chunk at offset {}: expected {:#010x}, got {:#010x}",
+                chunk_type, offset, expected, actual
+            ),
+            PngError::Truncated { offset } => {
+                write!(f, "unexpected end of data at offset {}", offset)
+            }
+            PngError::TrailingData { offset } => {
+                write!(f, "unexpected data after chunk at offset {}", offset)
+            }
+            PngError::ChunkTooLarge {
+                length,
+                max,
+                offset,
+            } => write!(
+                f,
+                "chunk at offset {} declares {} bytes of data, limit is {}",
+                offset, length, max
+            ),
+            PngError::InvalidChunkType(reason) => write!(f, "invalid chunk type: {}", reason),
+            PngError::ChunkNotFound(chunk_type) => write!(f, "chunk {} not found", chunk_type),
+            PngError::IndexOutOfRange { index, len } => {
+                write!(
+                    f,
+                    "chunk index {} is out of range, png has {} chunks",
+                    index, len
+                )
+            }
+            PngError::InvalidPosition(position) => {
+                write!(f, "invalid chunk position: {}", position)
+            }
+            PngError::InvalidOutput(reason) => write!(f, "invalid output: {}", reason),
+            PngError::InvalidFormat(format) => write!(f, "invalid format: {}", format),
+            PngError::InvalidIhdr(reason) => write!(f, "invalid IHDR: {}", reason),
+            PngError::StructureViolations(count) => {
+                write!(f, "png breaks {} structural rules", count)
+            }
+            PngError::InvalidText(reason) => write!(f, "invalid text chunk: {}", reason),
+            PngError::KeywordNotFound(keyword) => {
+                write!(f, "text with keyword {} not found", keyword)
+            }
+            PngError::Decompression(reason) => write!(f, "failed to decompress: {}", reason),
+            PngError::InvalidEnvelope(reason) => {
+                write!(f, "invalid encrypted envelope: {}", reason)
+            }
+            PngError::AuthenticationFailed => {
+                write!(f, "authentication failed, wrong key or tampered data")
+            }
+            PngError::InvalidKey(reason) => write!(f, "invalid key: {}", reason),
+            PngError::InvalidSignatureChunk(reason) => {
+                write!(f, "invalid signature chunk: {}", reason)
+            }
+            PngError::SignatureMismatch => {
+                write!(f, "signature doesn't match, png was changed after signing")
+            }
+            PngError::InvalidPayload(reason) => write!(f, "invalid payload: {}", reason),
+            PngError::UnsupportedImage(reason) => write!(f, "unsupported image: {}", reason),
+            PngError::InvalidImageData(reason) => write!(f, "invalid image data: {}", reason),
+            PngError::CapacityExceeded { needed, capacity } => write!(
+                f,
+                "payload needs {} bytes, image can hide only {}",
+                needed, capacity
+            ),
+            PngError::BinaryFile(Some(name)) => write!(f, "payload is the binary file {}", name),
+            PngError::BinaryFile(None) => write!(f, "payload is a binary file"),
+            PngError::EncryptedPayload => write!(f, "payload is encrypted"),
+            PngError::InvalidUtf8(_) => write!(f, "chunk data is not valid UTF-8"),
+            PngError::Io { path, .. } => write!(f, "i/o error on {}", path),
+        }
+    }
+}
+
+impl Error for PngError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PngError::InvalidUtf8(e) => Some(e),
+            PngError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<FromUtf8Error> for PngError {
+    fn from(value: FromUtf8Error) -> Self {
+        PngError::InvalidUtf8(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_at_offset_shifts_offsets() {
+        let err = PngError::Truncated { offset: 4 }.at_offset(8);
+        assert!(matches!(err, PngError::Truncated { offset: 12 }));
+
+        let err = PngError::CrcMismatch {
+            chunk_type: ChunkType::from_str("RuSt").unwrap(),
+            expected: 1,
+            actual: 2,
+            offset: 0,
+        }
+        .at_offset(33);
+        assert!(matches!(err, PngError::CrcMismatch { offset: 33, .. }));
+    }
+
+    #[test]
+    fn test_io_error_source() {
+        let err = PngError::Io {
+            path: String::from("dice.png"),
+            source: io::Error::new(io::ErrorKind::NotFound, "no such file"),
+        };
+        assert_eq!(err.to_string(), "i/o error on dice.png");
+        assert!(err.source().is_some());
+    }
+}
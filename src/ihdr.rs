@@ -0,0 +1,333 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, error::PngError};
+
+/// Color type of the image, as stored in `IHDR`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale = 0,
+    Rgb = 2,
+    Indexed = 3,
+    GrayscaleAlpha = 4,
+    Rgba = 6,
+}
+
+impl ColorType {
+    /// Bit depths allowed by the PNG specification for this color type.
+    pub fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            ColorType::Rgb | ColorType::GrayscaleAlpha | ColorType::Rgba => &[8, 16],
+        }
+    }
+
+    /// Number of samples per pixel.
+    pub fn channels(&self) -> usize {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    /// Whether the last sample of each pixel is alpha.
+    pub fn has_alpha(&self) -> bool {
+        matches!(self, ColorType::GrayscaleAlpha | ColorType::Rgba)
+    }
+}
+
+impl TryFrom<u8> for ColorType {
+    type Error = PngError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ColorType::Grayscale),
+            2 => Ok(ColorType::Rgb),
+            3 => Ok(ColorType::Indexed),
+            4 => Ok(ColorType::GrayscaleAlpha),
+            6 => Ok(ColorType::Rgba),
+            other => Err(PngError::InvalidIhdr(format!(
+                "unknown color type {}",
+                other
+            ))),
+        }
+    }
+}
+
+impl Display for ColorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ColorType::Grayscale => "grayscale",
+            ColorType::Rgb => "RGB",
+            ColorType::Indexed => "indexed",
+            ColorType::GrayscaleAlpha => "grayscale with alpha",
+            ColorType::Rgba => "RGBA",
+        };
+        write!(f, "{} ({})", name, *self as u8)
+    }
+}
+
+/// Image header, the first chunk of every png.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub compression: u8,
+    pub filter: u8,
+    pub interlace: u8,
+}
+
+impl Ihdr {
+    pub const LENGTH: u32 = 13;
+
+    pub fn is_interlaced(&self) -> bool {
+        self.interlace == 1
+    }
+
+    pub fn bits_per_pixel(&self) -> usize {
+        self.color_type.channels() * self.bit_depth as usize
+    }
+
+    /// Length in bytes of a row `width` pixels wide, without its filter byte.
+    pub fn row_length(&self, width: u32) -> usize {
+        (width as usize * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let data: Vec<u8> = self
+            .width
+            .to_be_bytes()
+            .iter()
+            .chain(self.height.to_be_bytes().iter())
+            .chain(
+                [
+                    self.bit_depth,
+                    self.color_type as u8,
+                    self.compression,
+                    self.filter,
+                    self.interlace,
+                ]
+                .iter(),
+            )
+            .copied()
+            .collect();
+
+        Chunk::new(
+            ChunkType::from_str("IHDR").expect("IHDR is a valid chunk type"),
+            data,
+        )
+    }
+
+    fn validate(&self) -> Result<(), PngError> {
+        let max_dimension = (1 << 31) - 1;
+        if self.width == 0 || self.width > max_dimension {
+            return Err(PngError::InvalidIhdr(format!(
+                "invalid width {}",
+                self.width
+            )));
+        }
+        if self.height == 0 || self.height > max_dimension {
+            return Err(PngError::InvalidIhdr(format!(
+                "invalid height {}",
+                self.height
+            )));
+        }
+        if !self
+            .color_type
+            .allowed_bit_depths()
+            .contains(&self.bit_depth)
+        {
+            return Err(PngError::InvalidIhdr(format!(
+                "bit depth {} is not allowed for color type {}",
+                self.bit_depth, self.color_type
+            )));
+        }
+        if self.compression != 0 {
+            return Err(PngError::InvalidIhdr(format!(
+                "unknown compression method {}",
+                self.compression
+            )));
+        }
+        if self.filter != 0 {
+            return Err(PngError::InvalidIhdr(format!(
+                "unknown filter method {}",
+                self.filter
+            )));
+        }
+        if self.interlace > 1 {
+            return Err(PngError::InvalidIhdr(format!(
+                "unknown interlace method {}",
+                self.interlace
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<&Chunk> for Ihdr {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        if &chunk.chunk_type().bytes() != b"IHDR" {
+            return Err(PngError::InvalidIhdr(format!(
+                "expected IHDR chunk, got {}",
+                chunk.chunk_type()
+            )));
+        }
+
+        let data = chunk.data();
+        if data.len() != Ihdr::LENGTH as usize {
+            return Err(PngError::InvalidIhdr(format!(
+                "IHDR must have {} bytes, got {}",
+                Ihdr::LENGTH,
+                data.len()
+            )));
+        }
+
+        let ihdr = Ihdr {
+            width: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            height: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            bit_depth: data[8],
+            color_type: ColorType::try_from(data[9])?,
+            compression: data[10],
+            filter: data[11],
+            interlace: data[12],
+        };
+        ihdr.validate()?;
+
+        Ok(ihdr)
+    }
+}
+
+impl Display for Ihdr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "width: {}", self.width)?;
+        writeln!(f, "height: {}", self.height)?;
+        writeln!(f, "bit depth: {}", self.bit_depth)?;
+        writeln!(f, "color type: {}", self.color_type)?;
+        writeln!(f, "compression: {} (deflate)", self.compression)?;
+        writeln!(f, "filter: {} (adaptive)", self.filter)?;
+        write!(
+            f,
+            "interlace: {} ({})",
+            self.interlace,
+            if self.is_interlaced() {
+                "adam7"
+            } else {
+                "none"
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::Png;
+
+    const PNG_FILE: &[u8] = include_bytes!("../test.png");
+
+    fn testing_ihdr() -> Ihdr {
+        Ihdr {
+            width: 50,
+            height: 40,
+            bit_depth: 8,
+            color_type: ColorType::Rgba,
+            compression: 0,
+            filter: 0,
+            interlace: 0,
+        }
+    }
+
+    #[test]
+    fn test_ihdr_from_png() {
+        let png = Png::try_from(PNG_FILE).unwrap();
+        let ihdr = Ihdr::try_from(png.chunks()[0]).unwrap();
+
+        assert_eq!(ihdr.width, 135);
+        assert_eq!(ihdr.height, 114);
+        assert_eq!(ihdr.bit_depth, 8);
+        assert_eq!(ihdr.color_type, ColorType::Rgb);
+        assert!(!ihdr.is_interlaced());
+    }
+
+    #[test]
+    fn test_ihdr_chunk_roundtrip() {
+        let ihdr = testing_ihdr();
+        let chunk = ihdr.to_chunk();
+
+        assert_eq!(chunk.length(), Ihdr::LENGTH);
+        assert_eq!(Ihdr::try_from(&chunk).unwrap(), ihdr);
+    }
+
+    #[test]
+    fn test_row_length() {
+        let ihdr = testing_ihdr();
+        assert_eq!(ihdr.bits_per_pixel(), 32);
+        assert_eq!(ihdr.row_length(ihdr.width), 200);
+
+        let ihdr = Ihdr {
+            bit_depth: 1,
+            color_type: ColorType::Grayscale,
+            ..testing_ihdr()
+        };
+        assert_eq!(ihdr.row_length(9), 2);
+        assert_eq!(ihdr.row_length(8), 1);
+        assert!(ColorType::Rgba.has_alpha());
+        assert!(!ColorType::Rgb.has_alpha());
+    }
+
+    #[test]
+    fn test_illegal_bit_depth() {
+        let ihdr = Ihdr {
+            bit_depth: 4,
+            ..testing_ihdr()
+        };
+        assert!(matches!(
+            Ihdr::try_from(&ihdr.to_chunk()),
+            Err(PngError::InvalidIhdr(_))
+        ));
+
+        let ihdr = Ihdr {
+            bit_depth: 16,
+            color_type: ColorType::Indexed,
+            ..testing_ihdr()
+        };
+        assert!(Ihdr::try_from(&ihdr.to_chunk()).is_err());
+    }
+
+    #[test]
+    fn test_invalid_ihdr_fields() {
+        for ihdr in [
+            Ihdr {
+                width: 0,
+                ..testing_ihdr()
+            },
+            Ihdr {
+                compression: 1,
+                ..testing_ihdr()
+            },
+            Ihdr {
+                interlace: 2,
+                ..testing_ihdr()
+            },
+        ] {
+            assert!(Ihdr::try_from(&ihdr.to_chunk()).is_err());
+        }
+
+        let mut data = testing_ihdr().to_chunk().data().to_vec();
+        data[9] = 5;
+        let chunk = Chunk::new(ChunkType::from_str("IHDR").unwrap(), data);
+        assert!(Ihdr::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_ihdr_from_other_chunk() {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![0; 13]);
+        assert!(Ihdr::try_from(&chunk).is_err());
+    }
+}
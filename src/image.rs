@@ -0,0 +1,846 @@
+use crate::{
+    chunk::MAX_CHUNK_LENGTH,
+    compression::{deflate, deflate_with_level, inflate, DEFAULT_DEFLATE_LEVEL},
+    error::PngError,
+    ihdr::{ColorType, Ihdr},
+};
+
+/// First column, first row, column spacing and row spacing of each Adam7 pass.
+const ADAM7: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// Filter type byte in front of every scanline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterType {
+    None = 0,
+    Sub = 1,
+    Up = 2,
+    Average = 3,
+    Paeth = 4,
+}
+
+impl TryFrom<u8> for FilterType {
+    type Error = PngError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(FilterType::None),
+            1 => Ok(FilterType::Sub),
+            2 => Ok(FilterType::Up),
+            3 => Ok(FilterType::Average),
+            4 => Ok(FilterType::Paeth),
+            other => Err(PngError::InvalidImageData(format!(
+                "unknown filter type {}",
+                other
+            ))),
+        }
+    }
+}
+
+impl FilterType {
+    pub const ALL: [FilterType; 5] = [
+        FilterType::None,
+        FilterType::Sub,
+        FilterType::Up,
+        FilterType::Average,
+        FilterType::Paeth,
+    ];
+}
+
+/// How `Png::set_image_data` picks the filter of each scanline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterStrategy {
+    /// The same filter for every row.
+    Fixed(FilterType),
+    /// The filter whose output has the minimum sum of absolute differences,
+    /// bytes taken as signed. Indexed images and bit depths below 8 always use
+    /// `None`, as the PNG specification recommends.
+    MinSum,
+}
+
+/// Options of `Png::set_image_data`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeOptions {
+    pub filter: FilterStrategy,
+    /// Deflate level from 0 to 9.
+    pub compression_level: u32,
+    /// Maximum data length of each `IDAT` chunk.
+    pub idat_length: usize,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions {
+            filter: FilterStrategy::MinSum,
+            compression_level: DEFAULT_DEFLATE_LEVEL,
+            idat_length: DEFAULT_IDAT_LENGTH,
+        }
+    }
+}
+
+/// Length of `IDAT` chunks written by default, the same as libpng.
+pub const DEFAULT_IDAT_LENGTH: usize = 8192;
+
+/// Unfiltered pixels of an image or of a single Adam7 pass, rows packed as in
+/// the PNG stream: samples are big-endian and pixels below 8 bits share bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawImage {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: ColorType,
+    data: Vec<u8>,
+}
+
+impl RawImage {
+    /// Wraps `data` holding `height` rows of `stride` bytes each.
+    pub fn new(
+        width: u32,
+        height: u32,
+        bit_depth: u8,
+        color_type: ColorType,
+        data: Vec<u8>,
+    ) -> Result<RawImage, PngError> {
+        if !color_type.allowed_bit_depths().contains(&bit_depth) {
+            return Err(PngError::InvalidImageData(format!(
+                "bit depth {} is not allowed for color type {}",
+                bit_depth, color_type
+            )));
+        }
+        let image = RawImage {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            data,
+        };
+        let expected = image.stride() * height as usize;
+        if image.data.len() != expected {
+            return Err(PngError::InvalidImageData(format!(
+                "{}x{} image needs {} bytes, got {}",
+                width,
+                height,
+                expected,
+                image.data.len()
+            )));
+        }
+        Ok(image)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn bit_depth(&self) -> u8 {
+        self.bit_depth
+    }
+
+    pub fn color_type(&self) -> ColorType {
+        self.color_type
+    }
+
+    pub fn bits_per_pixel(&self) -> usize {
+        self.color_type.channels() * self.bit_depth as usize
+    }
+
+    /// Length of a row in bytes.
+    pub fn stride(&self) -> usize {
+        (self.width as usize * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    /// Row `y`, or `None` past the last row.
+    pub fn row(&self, y: u32) -> Option<&[u8]> {
+        let stride = self.stride();
+        let start = y as usize * stride;
+        self.data
+            .get(start..start + stride)
+            .filter(|_| y < self.height)
+    }
+
+    pub fn row_mut(&mut self, y: u32) -> Option<&mut [u8]> {
+        let stride = self.stride();
+        let start = y as usize * stride;
+        match y < self.height {
+            true => self.data.get_mut(start..start + stride),
+            false => None,
+        }
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.data.chunks_exact(self.stride().max(1))
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Splits the image into its seven Adam7 passes, in the order an interlaced
+    /// png stores them. Passes of small images may be empty.
+    pub fn adam7_passes(&self) -> Vec<RawImage> {
+        let bits_per_pixel = self.bits_per_pixel();
+
+        ADAM7
+            .iter()
+            .map(|&(x0, y0, dx, dy)| {
+                let (width, height) = adam7_size(self.width, self.height, (x0, y0, dx, dy));
+                let mut pass = self.blank(width, height);
+                for y in 0..height {
+                    let source = self
+                        .row(y0 + y * dy)
+                        .expect("pass rows are inside the image");
+                    let target = pass.row_mut(y).expect("y is below pass height");
+                    for x in 0..width {
+                        copy_pixel(
+                            source,
+                            (x0 + x * dx) as usize,
+                            target,
+                            x as usize,
+                            bits_per_pixel,
+                        );
+                    }
+                }
+                pass
+            })
+            .collect()
+    }
+
+    /// Empty image sharing the pixel format of `self`.
+    fn blank(&self, width: u32, height: u32) -> RawImage {
+        let mut image = RawImage {
+            width,
+            height,
+            bit_depth: self.bit_depth,
+            color_type: self.color_type,
+            data: Vec::new(),
+        };
+        image.data = vec![0; image.stride() * height as usize];
+        image
+    }
+}
+
+/// Unfiltered rows of `row_length` bytes, along with the filter each row used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Scanlines {
+    pub(crate) row_length: usize,
+    pub(crate) filters: Vec<FilterType>,
+    pub(crate) data: Vec<u8>,
+}
+
+/// Distance in bytes between a byte and the same byte of the previous pixel,
+/// rounded up to 1 for bit depths below 8.
+pub(crate) fn filter_distance(ihdr: &Ihdr) -> usize {
+    ihdr.bits_per_pixel().div_ceil(8)
+}
+
+/// Width and height of every pass the image is stored in, a single one unless
+/// it's interlaced. Adam7 passes of small images may be empty.
+pub(crate) fn pass_sizes(ihdr: &Ihdr) -> Vec<(u32, u32)> {
+    if !ihdr.is_interlaced() {
+        return vec![(ihdr.width, ihdr.height)];
+    }
+    ADAM7
+        .iter()
+        .map(|&pass| adam7_size(ihdr.width, ihdr.height, pass))
+        .collect()
+}
+
+/// Size of an Adam7 pass of a `width` by `height` image.
+fn adam7_size(width: u32, height: u32, (x0, y0, dx, dy): (u32, u32, u32, u32)) -> (u32, u32) {
+    let span =
+        |length: u32, start: u32, step: u32| (length + step - 1).saturating_sub(start) / step;
+    (span(width, x0, dx), span(height, y0, dy))
+}
+
+/// Inflates the concatenated `IDAT` data and reverses the filters of every
+/// pass, each starting over from a row of zeros.
+pub(crate) fn decode_passes(ihdr: &Ihdr, idat: &[u8]) -> Result<Vec<Scanlines>, PngError> {
+    let sizes = pass_sizes(ihdr);
+    let filtered_length = |&(width, height): &(u32, u32)| match width {
+        0 => Some(0),
+        _ => (ihdr.row_length(width) + 1).checked_mul(height as usize),
+    };
+    let expected = sizes
+        .iter()
+        .try_fold(0usize, |sum, size| sum.checked_add(filtered_length(size)?))
+        .ok_or(PngError::UnsupportedImage(String::from(
+            "image is too large",
+        )))?;
+
+    let filtered = inflate(idat, expected)?;
+    if filtered.len() != expected {
+        return Err(PngError::InvalidImageData(format!(
+            "image data holds {} bytes, expected {}",
+            filtered.len(),
+            expected
+        )));
+    }
+
+    let mut offset = 0;
+    let mut passes = Vec::new();
+    for size in &sizes {
+        let length = filtered_length(size).expect("checked above");
+        passes.push(unfilter(
+            &filtered[offset..offset + length],
+            ihdr.row_length(size.0),
+            filter_distance(ihdr),
+        )?);
+        offset += length;
+    }
+    Ok(passes)
+}
+
+/// Inflates the concatenated `IDAT` data of a non-interlaced image and reverses
+/// its filters.
+pub(crate) fn decode_idat(ihdr: &Ihdr, idat: &[u8]) -> Result<Scanlines, PngError> {
+    if ihdr.is_interlaced() {
+        return Err(PngError::UnsupportedImage(String::from(
+            "interlaced images are not supported",
+        )));
+    }
+    Ok(decode_passes(ihdr, idat)?.remove(0))
+}
+
+/// Pixels of every pass the image is stored in, see `Png::image_passes`.
+pub(crate) fn decode_image_passes(ihdr: &Ihdr, idat: &[u8]) -> Result<Vec<RawImage>, PngError> {
+    decode_passes(ihdr, idat)?
+        .into_iter()
+        .zip(pass_sizes(ihdr))
+        .map(|(scanlines, (width, height))| {
+            RawImage::new(
+                width,
+                height,
+                ihdr.bit_depth,
+                ihdr.color_type,
+                scanlines.data,
+            )
+        })
+        .collect()
+}
+
+/// Pixels of the whole image, see `Png::image_data`.
+pub(crate) fn decode_image(ihdr: &Ihdr, idat: &[u8]) -> Result<RawImage, PngError> {
+    let mut passes = decode_image_passes(ihdr, idat)?;
+    if !ihdr.is_interlaced() {
+        return Ok(passes.remove(0));
+    }
+
+    let mut image = passes[0].blank(ihdr.width, ihdr.height);
+    for (pass, &(x0, y0, dx, dy)) in passes.iter().zip(&ADAM7) {
+        for y in 0..pass.height {
+            let source = pass.row(y).expect("y is below pass height");
+            let target = image
+                .row_mut(y0 + y * dy)
+                .expect("pass rows are inside the image");
+            for x in 0..pass.width {
+                copy_pixel(
+                    source,
+                    x as usize,
+                    target,
+                    (x0 + x * dx) as usize,
+                    ihdr.bits_per_pixel(),
+                );
+            }
+        }
+    }
+    Ok(image)
+}
+
+/// Copies pixel `source_x` of `source` to pixel `target_x` of `target`,
+/// including pixels packed several to a byte.
+fn copy_pixel(source: &[u8], source_x: usize, target: &mut [u8], target_x: usize, bits: usize) {
+    if bits >= 8 {
+        let length = bits / 8;
+        target[target_x * length..(target_x + 1) * length]
+            .copy_from_slice(&source[source_x * length..(source_x + 1) * length]);
+        return;
+    }
+
+    let mask = (1u8 << bits) - 1;
+    let source_shift = 8 - bits - source_x * bits % 8;
+    let value = (source[source_x * bits / 8] >> source_shift) & mask;
+    let target_shift = 8 - bits - target_x * bits % 8;
+    let byte = &mut target[target_x * bits / 8];
+    *byte = (*byte & !(mask << target_shift)) | (value << target_shift);
+}
+
+/// Filters and deflates `image` into `IDAT` data described by `ihdr`,
+/// interlacing it if needed.
+pub(crate) fn encode_image(
+    ihdr: &Ihdr,
+    image: &RawImage,
+    options: &EncodeOptions,
+) -> Result<Vec<u8>, PngError> {
+    if (image.width, image.height, image.bit_depth, image.color_type)
+        != (ihdr.width, ihdr.height, ihdr.bit_depth, ihdr.color_type)
+    {
+        return Err(PngError::InvalidImageData(format!(
+            "{}x{} {}-bit {} image doesn't match IHDR of {}x{} {}-bit {}",
+            image.width,
+            image.height,
+            image.bit_depth,
+            image.color_type,
+            ihdr.width,
+            ihdr.height,
+            ihdr.bit_depth,
+            ihdr.color_type
+        )));
+    }
+    if options.idat_length == 0 || options.idat_length > MAX_CHUNK_LENGTH as usize {
+        return Err(PngError::InvalidOutput(format!(
+            "IDAT chunks can't hold {} bytes",
+            options.idat_length
+        )));
+    }
+
+    let strategy = match options.filter {
+        FilterStrategy::MinSum if ihdr.color_type == ColorType::Indexed || ihdr.bit_depth < 8 => {
+            FilterStrategy::Fixed(FilterType::None)
+        }
+        strategy => strategy,
+    };
+    let passes = match ihdr.is_interlaced() {
+        true => image.adam7_passes(),
+        false => vec![image.clone()],
+    };
+
+    let mut filtered = Vec::new();
+    for pass in passes {
+        let scanlines = Scanlines {
+            row_length: pass.stride(),
+            filters: choose_filters(&pass, strategy, filter_distance(ihdr)),
+            data: pass.into_data(),
+        };
+        filtered.extend(filter(&scanlines, filter_distance(ihdr)));
+    }
+    Ok(deflate_with_level(&filtered, options.compression_level))
+}
+
+/// Filter of every row of `image` under `strategy`.
+fn choose_filters(image: &RawImage, strategy: FilterStrategy, distance: usize) -> Vec<FilterType> {
+    if let FilterStrategy::Fixed(filter_type) = strategy {
+        return vec![filter_type; image.height as usize];
+    }
+
+    let zeros = vec![0; image.stride()];
+    let mut previous = &zeros[..];
+    let mut filtered = Vec::with_capacity(image.stride());
+    let mut filters = Vec::with_capacity(image.height as usize);
+    for row in image.rows() {
+        let best = FilterType::ALL
+            .into_iter()
+            .min_by_key(|&candidate| {
+                filtered.clear();
+                filter_row(candidate, row, previous, distance, &mut filtered);
+                filtered
+                    .iter()
+                    .map(|&byte| (byte as i8).unsigned_abs() as u64)
+                    .sum::<u64>()
+            })
+            .expect("there are five filter types");
+        filters.push(best);
+        previous = row;
+    }
+    filters
+}
+
+/// Filters `scanlines` again with the filter each row used and deflates them.
+pub(crate) fn encode_idat(scanlines: &Scanlines, distance: usize) -> Vec<u8> {
+    deflate(&filter(scanlines, distance))
+}
+
+/// Reverses the filter of every row, each preceded by its filter type byte.
+pub(crate) fn unfilter(
+    filtered: &[u8],
+    row_length: usize,
+    distance: usize,
+) -> Result<Scanlines, PngError> {
+    let mut filters = Vec::new();
+    let mut data = Vec::with_capacity(filtered.len());
+    let mut previous = vec![0; row_length];
+
+    for row in filtered.chunks(row_length + 1) {
+        if row.len() != row_length + 1 {
+            return Err(PngError::InvalidImageData(String::from(
+                "last scanline is truncated",
+            )));
+        }
+        let filter = FilterType::try_from(row[0])?;
+        let mut current = row[1..].to_vec();
+        unfilter_row(filter, &mut current, &previous, distance);
+
+        filters.push(filter);
+        data.extend_from_slice(&current);
+        previous = current;
+    }
+
+    Ok(Scanlines {
+        row_length,
+        filters,
+        data,
+    })
+}
+
+/// Applies the filter of every row, prefixing it with the filter type byte.
+pub(crate) fn filter(scanlines: &Scanlines, distance: usize) -> Vec<u8> {
+    let row_length = scanlines.row_length;
+    if row_length == 0 {
+        // Empty Adam7 passes have no scanlines at all.
+        return Vec::new();
+    }
+    let mut filtered = Vec::with_capacity(scanlines.data.len() + scanlines.filters.len());
+    let zeros = vec![0; row_length];
+
+    for (index, (row, filter)) in scanlines
+        .data
+        .chunks(row_length)
+        .zip(&scanlines.filters)
+        .enumerate()
+    {
+        let previous = match index {
+            0 => &zeros[..],
+            _ => &scanlines.data[(index - 1) * row_length..index * row_length],
+        };
+        filtered.push(*filter as u8);
+        filter_row(*filter, row, previous, distance, &mut filtered);
+    }
+    filtered
+}
+
+fn unfilter_row(filter: FilterType, row: &mut [u8], previous: &[u8], distance: usize) {
+    for i in 0..row.len() {
+        let left = if i >= distance { row[i - distance] } else { 0 };
+        let up_left = if i >= distance {
+            previous[i - distance]
+        } else {
+            0
+        };
+        row[i] = row[i].wrapping_add(predict(filter, left, previous[i], up_left));
+    }
+}
+
+fn filter_row(filter: FilterType, row: &[u8], previous: &[u8], distance: usize, out: &mut Vec<u8>) {
+    for i in 0..row.len() {
+        let left = if i >= distance { row[i - distance] } else { 0 };
+        let up_left = if i >= distance {
+            previous[i - distance]
+        } else {
+            0
+        };
+        out.push(row[i].wrapping_sub(predict(filter, left, previous[i], up_left)));
+    }
+}
+
+/// Value a filter predicts for a byte from its unfiltered neighbours.
+fn predict(filter: FilterType, left: u8, up: u8, up_left: u8) -> u8 {
+    match filter {
+        FilterType::None => 0,
+        FilterType::Sub => left,
+        FilterType::Up => up,
+        FilterType::Average => ((left as u16 + up as u16) / 2) as u8,
+        FilterType::Paeth => paeth(left, up, up_left),
+    }
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let to_left = (estimate - left as i16).abs();
+    let to_up = (estimate - up as i16).abs();
+    let to_up_left = (estimate - up_left as i16).abs();
+
+    if to_left <= to_up && to_left <= to_up_left {
+        left
+    } else if to_up <= to_up_left {
+        up
+    } else {
+        up_left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+    use std::str::FromStr;
+
+    const PNG_FILE: &[u8] = include_bytes!("../test.png");
+
+    #[test]
+    fn test_filter_roundtrip() {
+        let filters = [
+            FilterType::None,
+            FilterType::Sub,
+            FilterType::Up,
+            FilterType::Average,
+            FilterType::Paeth,
+        ];
+        let scanlines = Scanlines {
+            row_length: 12,
+            filters: filters.to_vec(),
+            data: (0..60).map(|i| (i * 37 % 256) as u8).collect(),
+        };
+
+        let filtered = filter(&scanlines, 3);
+        assert_eq!(filtered.len(), 65);
+        assert_eq!(unfilter(&filtered, 12, 3).unwrap(), scanlines);
+    }
+
+    #[test]
+    fn test_paeth() {
+        assert_eq!(paeth(10, 20, 10), 20);
+        assert_eq!(paeth(20, 10, 10), 20);
+        assert_eq!(paeth(10, 10, 20), 10);
+        assert_eq!(paeth(200, 10, 100), 100);
+    }
+
+    #[test]
+    fn test_decode_idat() {
+        let png = Png::try_from(PNG_FILE).unwrap();
+        let ihdr = png.header_info().unwrap();
+
+        let scanlines = decode_idat(&ihdr, &png.image_stream()).unwrap();
+        assert_eq!(scanlines.data.len(), 135 * 3 * 114);
+        assert_eq!(scanlines.filters.len(), 114);
+
+        let encoded = encode_idat(&scanlines, filter_distance(&ihdr));
+        assert_eq!(decode_idat(&ihdr, &encoded).unwrap(), scanlines);
+    }
+
+    /// Interlaced `IDAT` data of `image`, every pass filtered with Paeth.
+    fn interlaced_stream(image: &RawImage) -> Vec<u8> {
+        let distance = image.bits_per_pixel().div_ceil(8);
+        let filtered: Vec<u8> = image
+            .adam7_passes()
+            .into_iter()
+            .flat_map(|pass| {
+                filter(
+                    &Scanlines {
+                        row_length: pass.stride(),
+                        filters: vec![FilterType::Paeth; pass.height() as usize],
+                        data: pass.into_data(),
+                    },
+                    distance,
+                )
+            })
+            .collect();
+        deflate(&filtered)
+    }
+
+    #[test]
+    fn test_image_data() {
+        let png = Png::try_from(PNG_FILE).unwrap();
+        let image = png.image_data().unwrap();
+
+        assert_eq!((image.width(), image.height()), (135, 114));
+        assert_eq!(image.color_type(), ColorType::Rgb);
+        assert_eq!(image.stride(), 405);
+        assert_eq!(image.rows().count(), 114);
+        assert_eq!(image.row(1).unwrap(), &image.data()[405..810]);
+        assert!(image.row(114).is_none());
+        assert_eq!(png.image_passes().unwrap(), vec![image]);
+    }
+
+    #[test]
+    fn test_adam7_image_data() {
+        for (width, height, bit_depth, color_type) in [
+            (16, 9, 1, ColorType::Grayscale),
+            (11, 7, 8, ColorType::Rgb),
+            (3, 3, 16, ColorType::Rgba),
+            (1, 1, 8, ColorType::GrayscaleAlpha),
+        ] {
+            let ihdr = Ihdr {
+                width,
+                height,
+                bit_depth,
+                color_type,
+                compression: 0,
+                filter: 0,
+                interlace: 1,
+            };
+            let length = ihdr.row_length(width) * height as usize;
+            let data = (0..length).map(|i| (i * 37 % 256) as u8).collect();
+            let image = RawImage::new(width, height, bit_depth, color_type, data).unwrap();
+
+            let stream = interlaced_stream(&image);
+            assert_eq!(decode_image(&ihdr, &stream).unwrap(), image);
+
+            let passes = decode_image_passes(&ihdr, &stream).unwrap();
+            assert_eq!(passes.len(), 7);
+            assert_eq!(passes, image.adam7_passes());
+            let sizes: Vec<_> = passes
+                .iter()
+                .map(|pass| (pass.width(), pass.height()))
+                .collect();
+            assert_eq!(sizes, pass_sizes(&ihdr));
+        }
+    }
+
+    #[test]
+    fn test_adam7_pass_sizes() {
+        let mut ihdr = Png::try_from(PNG_FILE).unwrap().header_info().unwrap();
+        ihdr.interlace = 1;
+        ihdr.width = 1;
+        ihdr.height = 1;
+        assert_eq!(
+            pass_sizes(&ihdr),
+            [(1, 1), (0, 1), (1, 0), (0, 1), (1, 0), (0, 1), (1, 0)]
+        );
+
+        ihdr.width = 9;
+        ihdr.height = 10;
+        assert_eq!(
+            pass_sizes(&ihdr),
+            [(2, 2), (1, 2), (3, 1), (2, 3), (5, 2), (4, 5), (9, 5)]
+        );
+    }
+
+    #[test]
+    fn test_raw_image_length() {
+        assert!(matches!(
+            RawImage::new(2, 2, 8, ColorType::Rgb, vec![0; 11]),
+            Err(PngError::InvalidImageData(_))
+        ));
+        assert!(RawImage::new(2, 2, 4, ColorType::Rgb, vec![0; 12]).is_err());
+
+        let mut image = RawImage::new(9, 2, 1, ColorType::Grayscale, vec![0; 4]).unwrap();
+        assert_eq!(image.stride(), 2);
+        image.row_mut(1).unwrap()[0] = 0xff;
+        assert_eq!(image.data(), [0, 0, 0xff, 0]);
+        assert!(image.row_mut(2).is_none());
+    }
+
+    fn gradient_png(interlace: u8) -> Png {
+        let ihdr = Ihdr {
+            width: 33,
+            height: 20,
+            bit_depth: 8,
+            color_type: ColorType::Rgb,
+            compression: 0,
+            filter: 0,
+            interlace,
+        };
+        Png::from_chunks(vec![
+            ihdr.to_chunk(),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), Vec::new()),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()),
+        ])
+    }
+
+    fn gradient_image() -> RawImage {
+        let data = (0..20u32)
+            .flat_map(|y| (0..33 * 3u32).map(move |x| (x * 2 + y * 5) as u8))
+            .collect();
+        RawImage::new(33, 20, 8, ColorType::Rgb, data).unwrap()
+    }
+
+    #[test]
+    fn test_set_image_data() {
+        let mut png = Png::try_from(PNG_FILE).unwrap();
+        let image = png.image_data().unwrap();
+        let options = EncodeOptions {
+            filter: FilterStrategy::Fixed(FilterType::Up),
+            compression_level: 9,
+            idat_length: 1000,
+        };
+
+        png.set_image_data(image.clone(), options).unwrap();
+        assert_eq!(png.image_data().unwrap(), image);
+        let ihdr = png.header_info().unwrap();
+        let passes = decode_passes(&ihdr, &png.image_stream()).unwrap();
+        assert!(passes[0].filters.iter().all(|f| *f == FilterType::Up));
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(types[..4], ["IHDR", "sRGB", "gAMA", "pHYs"]);
+        assert_eq!(types.last().unwrap(), "IEND");
+        let idat = png.chunks_by_type("IDAT");
+        assert_eq!(idat.len(), png.image_stream().len().div_ceil(1000));
+        assert!(idat.iter().all(|chunk| chunk.length() <= 1000));
+    }
+
+    #[test]
+    fn test_min_sum_filters() {
+        let mut png = gradient_png(0);
+        let image = gradient_image();
+
+        png.set_image_data(image.clone(), EncodeOptions::default())
+            .unwrap();
+        assert_eq!(png.image_data().unwrap(), image);
+        let ihdr = png.header_info().unwrap();
+        let min_sum = png.image_stream();
+        let filters = &decode_passes(&ihdr, &min_sum).unwrap()[0].filters;
+        assert!(!filters.contains(&FilterType::None));
+
+        let none = EncodeOptions {
+            filter: FilterStrategy::Fixed(FilterType::None),
+            ..EncodeOptions::default()
+        };
+        png.set_image_data(image, none).unwrap();
+        assert!(min_sum.len() < png.image_stream().len());
+    }
+
+    #[test]
+    fn test_set_interlaced_image_data() {
+        let mut png = gradient_png(1);
+        let image = gradient_image();
+
+        png.set_image_data(image.clone(), EncodeOptions::default())
+            .unwrap();
+        assert_eq!(png.image_data().unwrap(), image);
+        assert_eq!(png.image_passes().unwrap(), image.adam7_passes());
+    }
+
+    #[test]
+    fn test_set_image_data_mismatch() {
+        let mut png = gradient_png(0);
+        let image = RawImage::new(33, 20, 8, ColorType::Rgba, vec![0; 33 * 20 * 4]).unwrap();
+        assert!(matches!(
+            png.set_image_data(image, EncodeOptions::default()),
+            Err(PngError::InvalidImageData(_))
+        ));
+
+        let options = EncodeOptions {
+            idat_length: 0,
+            ..EncodeOptions::default()
+        };
+        assert!(matches!(
+            png.set_image_data(gradient_image(), options),
+            Err(PngError::InvalidOutput(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_image_data() {
+        assert!(matches!(
+            unfilter(&[5, 0, 0], 2, 1),
+            Err(PngError::InvalidImageData(_))
+        ));
+        assert!(matches!(
+            unfilter(&[0, 0, 0, 0], 2, 1),
+            Err(PngError::InvalidImageData(_))
+        ));
+
+        let ihdr = Png::try_from(PNG_FILE).unwrap().header_info().unwrap();
+        assert!(matches!(
+            decode_idat(&ihdr, &deflate(&[0; 10])),
+            Err(PngError::InvalidImageData(_))
+        ));
+    }
+}
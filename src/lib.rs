@@ -0,0 +1,29 @@
+pub mod chunk;
+pub mod chunk_type;
+pub mod commands;
+pub mod compression;
+pub mod crypto;
+pub mod error;
+pub mod ihdr;
+pub mod image;
+pub mod lint;
+pub mod payload;
+pub mod png;
+pub mod reader;
+pub mod signature;
+pub mod stego;
+pub mod strip;
+pub mod text;
+pub mod writer;
+
+pub use chunk::Chunk;
+pub use chunk_type::ChunkType;
+pub use error::PngError;
+pub use ihdr::{ColorType, Ihdr};
+pub use image::{EncodeOptions, FilterStrategy, FilterType, RawImage};
+pub use lint::{Severity, Violation};
+pub use png::{ChunkPosition, Png};
+pub use reader::ChunkReader;
+pub use strip::{StripPreset, StrippedChunk};
+pub use text::{CompressedTextChunk, InternationalTextChunk, TextChunk};
+pub use writer::ChunkWriter;
@@ -0,0 +1,315 @@
+use std::fmt::Display;
+
+use crate::{chunk::Chunk, ihdr::ColorType, ihdr::Ihdr};
+
+/// Ancillary chunks that must appear before `PLTE`.
+const BEFORE_PLTE: [&[u8; 4]; 5] = [b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB"];
+
+/// Ancillary chunks that must appear before the first `IDAT`.
+const BEFORE_IDAT: [&[u8; 4]; 13] = [
+    b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB", b"bKGD", b"hIST", b"tRNS", b"pHYs", b"sPLT",
+    b"oFFs", b"pCAL", b"sCAL",
+];
+
+/// Ancillary chunks that must appear after `PLTE`, when there is one.
+const AFTER_PLTE: [&[u8; 4]; 3] = [b"bKGD", b"hIST", b"tRNS"];
+
+/// Chunks that may appear at most once.
+const UNIQUE: [&[u8; 4]; 14] = [
+    b"IHDR", b"PLTE", b"IEND", b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB", b"bKGD", b"hIST",
+    b"tRNS", b"pHYs", b"tIME", b"eXIf",
+];
+
+/// Critical chunks defined by the PNG specification.
+const KNOWN_CRITICAL: [&[u8; 4]; 4] = [b"IHDR", b"PLTE", b"IDAT", b"IEND"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Decoders are allowed to reject the file.
+    Error,
+    /// The file decodes, but breaks a recommendation of the specification.
+    Warning,
+}
+
+/// Single broken ordering or multiplicity rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub severity: Severity,
+    /// Index of the offending chunk, `None` for problems of the file as a whole.
+    pub chunk_index: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.chunk_index {
+            Some(index) => write!(f, "{}: chunk {}: {}", severity, index, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+/// Checks chunk ordering and multiplicity rules of the PNG specification,
+/// returning every violation found.
+pub fn validate_chunks(chunks: &[Chunk]) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let mut error = |chunk_index: Option<usize>, message: String| {
+        violations.push(Violation {
+            severity: Severity::Error,
+            chunk_index,
+            message,
+        })
+    };
+
+    let position = |chunk_type: &[u8; 4]| {
+        chunks
+            .iter()
+            .position(|chunk| &chunk.chunk_type().bytes() == chunk_type)
+    };
+    let first_idat = position(b"IDAT");
+    let plte = position(b"PLTE");
+
+    match chunks.first() {
+        Some(chunk) if &chunk.chunk_type().bytes() == b"IHDR" => {
+            if let Err(e) = Ihdr::try_from(chunk) {
+                error(Some(0), e.to_string());
+            }
+        }
+        _ => error(None, String::from("IHDR must be the first chunk")),
+    }
+
+    match position(b"IEND") {
+        Some(idx) if idx + 1 != chunks.len() => error(
+            Some(idx + 1),
+            format!("{} chunks found after IEND", chunks.len() - idx - 1),
+        ),
+        Some(_) => {}
+        None => error(None, String::from("IEND chunk is missing")),
+    }
+
+    match first_idat {
+        Some(first) => {
+            let mut idat_run_ended = false;
+            for (idx, chunk) in chunks.iter().enumerate().skip(first) {
+                let is_idat = &chunk.chunk_type().bytes() == b"IDAT";
+                if is_idat && idat_run_ended {
+                    error(Some(idx), String::from("IDAT chunks must be consecutive"));
+                    break;
+                }
+                idat_run_ended = !is_idat;
+            }
+        }
+        None => error(None, String::from("IDAT chunk is missing")),
+    }
+
+    let color_type = chunks
+        .first()
+        .and_then(|chunk| Ihdr::try_from(chunk).ok())
+        .map(|ihdr| ihdr.color_type);
+    match (plte, color_type) {
+        (None, Some(ColorType::Indexed)) => error(
+            None,
+            String::from("PLTE chunk is required for indexed color"),
+        ),
+        (Some(idx), Some(ColorType::Grayscale | ColorType::GrayscaleAlpha)) => error(
+            Some(idx),
+            String::from("PLTE chunk is not allowed for grayscale images"),
+        ),
+        _ => {}
+    }
+
+    for (idx, chunk) in chunks.iter().enumerate() {
+        let chunk_type = chunk.chunk_type();
+        let bytes = chunk_type.bytes();
+
+        if !chunk_type.is_reserved_bit_valid() {
+            error(
+                Some(idx),
+                format!("{} has the reserved bit set", chunk_type),
+            );
+        }
+
+        if chunk_type.is_critical() && !KNOWN_CRITICAL.contains(&&bytes) {
+            error(Some(idx), format!("unknown critical chunk {}", chunk_type));
+        }
+
+        if idx > 0 && &bytes == b"IHDR" {
+            error(Some(idx), String::from("IHDR must be the first chunk"));
+        }
+
+        if UNIQUE.contains(&&bytes)
+            && chunks[..idx]
+                .iter()
+                .any(|other| other.chunk_type() == chunk_type)
+        {
+            error(Some(idx), format!("{} may appear at most once", chunk_type));
+            continue;
+        }
+
+        let after_idat = first_idat.is_some_and(|first| idx > first);
+        if (&bytes == b"PLTE" || BEFORE_IDAT.contains(&&bytes)) && after_idat {
+            error(
+                Some(idx),
+                format!("{} must come before the first IDAT", chunk_type),
+            );
+        }
+
+        if let Some(plte) = plte {
+            if BEFORE_PLTE.contains(&&bytes) && idx > plte {
+                error(Some(idx), format!("{} must come before PLTE", chunk_type));
+            }
+            if AFTER_PLTE.contains(&&bytes) && idx < plte {
+                error(Some(idx), format!("{} must come after PLTE", chunk_type));
+            }
+        }
+    }
+
+    if let (Some(srgb), Some(_)) = (position(b"sRGB"), position(b"iCCP")) {
+        violations.push(Violation {
+            severity: Severity::Warning,
+            chunk_index: Some(srgb),
+            message: String::from("sRGB and iCCP should not both be present"),
+        });
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk_type::ChunkType, png::Png};
+    use std::str::FromStr;
+
+    const PNG_FILE: &[u8] = include_bytes!("../test.png");
+
+    fn chunk(chunk_type: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), Vec::new())
+    }
+
+    fn testing_chunks() -> Vec<Chunk> {
+        Png::try_from(PNG_FILE)
+            .unwrap()
+            .chunks()
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    fn messages(chunks: &[Chunk]) -> Vec<String> {
+        validate_chunks(chunks)
+            .iter()
+            .map(|violation| violation.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_png() {
+        assert!(validate_chunks(&testing_chunks()).is_empty());
+    }
+
+    #[test]
+    fn test_missing_ihdr_and_iend() {
+        let chunks = vec![chunk("IDAT")];
+        assert_eq!(
+            messages(&chunks),
+            [
+                "error: IHDR must be the first chunk",
+                "error: IEND chunk is missing"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_data_after_iend() {
+        let mut chunks = testing_chunks();
+        chunks.push(chunk("ruSt"));
+        assert_eq!(
+            messages(&chunks),
+            ["error: chunk 6: 1 chunks found after IEND"]
+        );
+    }
+
+    #[test]
+    fn test_multiple_iend() {
+        let mut chunks = testing_chunks();
+        chunks.push(chunk("IEND"));
+        assert_eq!(
+            messages(&chunks),
+            [
+                "error: chunk 6: 1 chunks found after IEND",
+                "error: chunk 6: IEND may appear at most once"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_idat() {
+        let mut chunks = testing_chunks();
+        chunks.insert(5, chunk("tEXt"));
+        chunks.insert(6, chunk("IDAT"));
+        assert_eq!(
+            messages(&chunks),
+            ["error: chunk 6: IDAT chunks must be consecutive"]
+        );
+    }
+
+    #[test]
+    fn test_ordering_rules() {
+        let mut chunks = testing_chunks();
+        chunks.insert(5, chunk("PLTE"));
+        chunks.insert(6, chunk("tIME"));
+        chunks.insert(7, chunk("tIME"));
+        chunks.insert(8, chunk("pHYs"));
+        assert_eq!(
+            messages(&chunks),
+            [
+                "error: chunk 5: PLTE must come before the first IDAT",
+                "error: chunk 7: tIME may appear at most once",
+                "error: chunk 8: pHYs may appear at most once",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_palette_ordering_rules() {
+        let mut chunks = testing_chunks();
+        chunks.insert(1, chunk("tRNS"));
+        chunks.insert(2, chunk("PLTE"));
+        assert_eq!(
+            messages(&chunks),
+            [
+                "error: chunk 1: tRNS must come after PLTE",
+                "error: chunk 3: sRGB must come before PLTE",
+                "error: chunk 4: gAMA must come before PLTE",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_critical_and_reserved_bit() {
+        let mut chunks = testing_chunks();
+        chunks.insert(1, chunk("RUST"));
+        chunks.insert(2, chunk("ruse"));
+        assert_eq!(
+            messages(&chunks),
+            [
+                "error: chunk 1: unknown critical chunk RUST",
+                "error: chunk 2: ruse has the reserved bit set",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_srgb_with_iccp_is_warning() {
+        let mut chunks = testing_chunks();
+        chunks.insert(1, chunk("iCCP"));
+        let violations = validate_chunks(&chunks);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].severity, Severity::Warning);
+    }
+}
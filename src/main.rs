@@ -1,10 +1,19 @@
-use clap::{Arg, ArgAction, Command};
-use commands::{decode, encode, get_chunks, validate, remove_chunk};
+use std::{error::Error, path::Path, process, str::FromStr};
 
-pub mod chunk;
-pub mod chunk_type;
-pub mod commands;
-pub mod png;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use pngme::chunk::MAX_CHUNK_LENGTH;
+use pngme::commands::{
+    chunk_infos, decode, describe_chunk_type, encode, extract, format_chunk_infos, get_chunks,
+    hide, info, keygen, lint, read_identity, read_payload_file, read_signing_key, remove_chunks,
+    reveal, save_payload, sign, signing_keygen, strip_metadata, text_delete, text_get, text_list,
+    text_set, validate, verify, ChunkFilter, DataEncoding, ListFormat, Occurrence, Output,
+    PayloadOptions, STDIN_PATH,
+};
+use pngme::compression::Codec;
+use pngme::crypto::{Decryption, Encryption, Recipient};
+use pngme::payload::{FileEnvelope, Payload, FRAGMENT_HEADER_LENGTH};
+use pngme::signature::{ScopeKind, SignatureScope, VerifyingKey};
+use pngme::{ChunkPosition, ChunkType, PngError, Severity, StripPreset};
 
 fn main() {
     let matches = Command::new("pngme")
@@ -12,22 +21,106 @@ fn main() {
         .subcommand(
             Command::new("encode")
                 .about("encodes data in the chunk")
-                .arg(Arg::new("path").required(true).help("path to png file"))
+                .arg(Arg::new("path").required(true).help("path to png file, - reads it from stdin"))
                 .arg(
                     Arg::new("type")
                         .required(true)
                         .help("valid chunk type e.g ruSt"),
                 )
-                .arg(Arg::new("data").required(false).help("data to be encoded")),
+                .args(payload_args())
+                .arg(
+                    Arg::new("position")
+                        .long("position")
+                        .default_value("before-iend")
+                        .help("where to put the chunk: before-iend, after-ihdr, before-idat, end or chunk index"),
+                )
+                .arg(
+                    Arg::new("max-chunk-size")
+                        .long("max-chunk-size")
+                        .value_name("BYTES")
+                        .value_parser(value_parser!(u32).range(FRAGMENT_HEADER_LENGTH as i64 + 1..=i64::from(MAX_CHUNK_LENGTH)))
+                        .default_value("1048576")
+                        .help("splits longer data across several chunks of the same type"),
+                )
+                .args(output_args()),
         )
         .subcommand(
             Command::new("decode")
                 .about("prints data from the given chunk type")
-                .arg(Arg::new("path").required(true).help("path to png file"))
+                .arg(Arg::new("path").required(true).help("path to png file, - reads it from stdin"))
                 .arg(
                     Arg::new("type")
                         .required(true)
                         .help("valid chunk type e.g ruSt"),
+                )
+                .args(decryption_args()),
+        )
+        .subcommand(
+            Command::new("hide")
+                .about("hides data in the least significant bits of the pixels, leaving no chunk behind")
+                .arg(Arg::new("path").required(true).help("path to png file, - reads it from stdin"))
+                .args(payload_args())
+                .args(output_args()),
+        )
+        .subcommand(
+            Command::new("reveal")
+                .about("prints data hidden in the pixels with hide")
+                .arg(Arg::new("path").required(true).help("path to png file, - reads it from stdin"))
+                .args(decryption_args()),
+        )
+        .subcommand(
+            Command::new("keygen")
+                .about("generates a key pair for --recipient and --identity")
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .default_value("-")
+                        .help("path of the new key file, - writes it to stdout"),
+                )
+                .arg(
+                    Arg::new("signing")
+                        .long("signing")
+                        .action(ArgAction::SetTrue)
+                        .help("generates an Ed25519 key for sign and verify instead"),
+                ),
+        )
+        .subcommand(
+            Command::new("sign")
+                .about("signs png chunks with an Ed25519 key and stores the signature in a siGN chunk")
+                .arg(Arg::new("path").required(true).help("path to png file, - reads it from stdin"))
+                .arg(
+                    Arg::new("key")
+                        .long("key")
+                        .short('k')
+                        .required(true)
+                        .help("key file written by keygen --signing"),
+                )
+                .arg(
+                    Arg::new("scope")
+                        .long("scope")
+                        .value_parser(["all", "critical", "unsafe-to-copy"])
+                        .default_value("all")
+                        .help("chunks covered by the signature, unsafe-to-copy allows edits of safe-to-copy chunks"),
+                )
+                .arg(
+                    Arg::new("include")
+                        .long("include")
+                        .action(ArgAction::Append)
+                        .help("chunk type covered on top of the scope e.g. ruSt, may be repeated"),
+                )
+                .args(output_args()),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("checks siGN signatures, failing if the png was changed after signing")
+                .after_help("Without --key only integrity is checked: anyone can edit the image and re-sign it with their own key. Pass --key to prove who signed it.")
+                .arg(Arg::new("path").required(true).help("path to png file, - reads it from stdin"))
+                .arg(
+                    Arg::new("key")
+                        .long("key")
+                        .short('k')
+                        .help("public key printed by keygen --signing, proves who signed the png. Otherwise every signature is only checked against its embedded key"),
                 ),
         )
         .subcommand(
@@ -39,17 +132,118 @@ fn main() {
         )
         .subcommand(
             Command::new("chunks").about("prints list of chunk types")
-                .arg(Arg::new("path").required(true).help("path to png file"))
+                .arg(Arg::new("path").required(true).help("path to png file, - reads it from stdin"))
                 .arg(Arg::new("with-data")
                     .help("prints chunk's data as string if it's valid UTF-8 message, otherwise it prints data as bytes")
                     .short('d')
                     .action(ArgAction::SetTrue)
             )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["json", "jsonl", "table"])
+                        .help("prints chunk records with index, offset, length, crc and property bits"),
+                )
+                .arg(
+                    Arg::new("encoding")
+                        .long("encoding")
+                        .value_parser(["utf8", "hex", "base64"])
+                        .help("encoding of chunk's data in --format output, implies -d"),
+                )
+        )
+        .subcommand(
+            Command::new("info")
+                .about("prints image header")
+                .arg(Arg::new("path").required(true).help("path to png file, - reads it from stdin")),
+        )
+        .subcommand(
+            Command::new("lint")
+                .about("checks chunk ordering and multiplicity rules of the PNG specification")
+                .arg(Arg::new("path").required(true).help("path to png file, - reads it from stdin")),
         )
         .subcommand(
             Command::new("remove").about("removes chunk from png")
-                .arg(Arg::new("path").required(true).help("path to png file"))
+                .arg(Arg::new("path").required(true).help("path to png file, - reads it from stdin"))
                 .arg(Arg::new("type").required(true).help("chunk type to be removed"))
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .action(ArgAction::SetTrue)
+                        .help("removes every matching chunk instead of the first one"),
+                )
+                .arg(
+                    Arg::new("index")
+                        .long("index")
+                        .value_name("N")
+                        .value_parser(value_parser!(usize))
+                        .conflicts_with("all")
+                        .help("removes only the Nth matching chunk, counting from 0"),
+                )
+                .arg(
+                    Arg::new("where")
+                        .long("where")
+                        .value_name("FILTER")
+                        .action(ArgAction::Append)
+                        .help("removes only chunks matching keyword=<keyword>, size>N or size<N, may be repeated"),
+                )
+                .args(output_args())
+        )
+        .subcommand(
+            Command::new("strip").about("removes metadata that could identify people, critical chunks are always kept")
+                .arg(Arg::new("path").required(true).help("path to png file, - reads it from stdin"))
+                .arg(
+                    Arg::new("privacy")
+                        .long("privacy")
+                        .action(ArgAction::SetTrue)
+                        .help("removes identifying text, eXIf, tIME, iCCP and private chunks (default)"),
+                )
+                .arg(
+                    Arg::new("all-ancillary")
+                        .long("all-ancillary")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("privacy")
+                        .help("removes every ancillary chunk"),
+                )
+                .arg(
+                    Arg::new("keep")
+                        .long("keep")
+                        .value_name("TYPES")
+                        .value_delimiter(',')
+                        .action(ArgAction::Append)
+                        .help("comma separated chunk types that are never removed"),
+                )
+                .args(output_args())
+        )
+        .subcommand(
+            Command::new("text")
+                .about("reads and writes tEXt keyword/value pairs")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("list")
+                        .about("prints every keyword and its text")
+                        .arg(Arg::new("path").required(true).help("path to png file, - reads it from stdin")),
+                )
+                .subcommand(
+                    Command::new("get")
+                        .about("prints text of the first chunk with the keyword")
+                        .arg(Arg::new("path").required(true).help("path to png file, - reads it from stdin"))
+                        .arg(Arg::new("keyword").required(true).help("keyword e.g. Title")),
+                )
+                .subcommand(
+                    Command::new("set")
+                        .about("sets text of the keyword, replacing the existing chunk")
+                        .arg(Arg::new("path").required(true).help("path to png file, - reads it from stdin"))
+                        .arg(Arg::new("keyword").required(true).help("keyword e.g. Title"))
+                        .arg(Arg::new("text").required(true).help("Latin-1 text"))
+                        .args(output_args()),
+                )
+                .subcommand(
+                    Command::new("delete")
+                        .about("removes every chunk with the keyword")
+                        .arg(Arg::new("path").required(true).help("path to png file, - reads it from stdin"))
+                        .arg(Arg::new("keyword").required(true).help("keyword e.g. Title"))
+                        .args(output_args()),
+                ),
         )
         .get_matches();
 
@@ -65,13 +259,30 @@ fn main() {
                 .map(|s| s.as_str())
                 .expect("type is required");
 
-            let chunk_data = encode_matches
-                .get_one::<String>("data")
+            let payload = payload_from(encode_matches);
+
+            let position = encode_matches
+                .get_one::<String>("position")
                 .map(|s| s.as_str())
-                .unwrap_or("");
+                .expect("position has default value");
 
-            if let Err(e) = encode(path, chunk_type, chunk_data) {
-                println!("failed to encode file {}: {}", path, e);
+            let output = output_from(encode_matches, path, "encoded.png");
+
+            let max_chunk_length = *encode_matches
+                .get_one::<u32>("max-chunk-size")
+                .expect("max-chunk-size has default value");
+
+            let result = ChunkPosition::from_str(position).and_then(|position| {
+                let options = PayloadOptions {
+                    position,
+                    compression: compression_from(encode_matches)?,
+                    encryption: encryption_from(encode_matches)?,
+                    max_chunk_length: max_chunk_length as usize,
+                };
+                encode(path, chunk_type, &payload, &options, &output)
+            });
+            if let Err(e) = result {
+                fail(&format!("failed to encode file {}", path), e);
             }
         }
         Some(("decode", encode_matches)) => {
@@ -85,9 +296,173 @@ fn main() {
                 .map(|s| s.as_str())
                 .expect("type is required");
 
-            match decode(path, chunk_type) {
+            let decryption = decryption_from(encode_matches)
+                .unwrap_or_else(|e| fail("failed to decode message", e));
+
+            let max_size = *encode_matches
+                .get_one::<usize>("max-size")
+                .expect("max-size has default value");
+
+            if let Some(out) = encode_matches.get_one::<String>("out") {
+                match extract(path, chunk_type, decryption.as_ref(), max_size, out) {
+                    Ok((written, envelope)) => report_saved(&written, &envelope),
+                    Err(e) => fail_payload("failed to extract data", e),
+                }
+                return;
+            }
+
+            match decode(path, chunk_type, decryption.as_ref(), max_size) {
                 Ok(decoded_message) => println!("{}", decoded_message),
-                Err(e) => println!("failed to decode message: {}", e),
+                Err(e) => fail_payload("failed to decode message", e),
+            }
+        }
+        Some(("hide", hide_matches)) => {
+            let path = hide_matches
+                .get_one::<String>("path")
+                .map(|s| s.as_str())
+                .expect("path is required");
+
+            let payload = payload_from(hide_matches);
+            let output = output_from(hide_matches, path, "hidden.png");
+
+            let result = compression_from(hide_matches).and_then(|compression| {
+                let encryption = encryption_from(hide_matches)?;
+                hide(path, &payload, compression, encryption.as_ref(), &output)
+            });
+            if let Err(e) = result {
+                fail(&format!("failed to hide data in {}", path), e);
+            }
+        }
+        Some(("reveal", reveal_matches)) => {
+            let path = reveal_matches
+                .get_one::<String>("path")
+                .map(|s| s.as_str())
+                .expect("path is required");
+
+            let max_size = *reveal_matches
+                .get_one::<usize>("max-size")
+                .expect("max-size has default value");
+
+            let payload = decryption_from(reveal_matches)
+                .and_then(|decryption| reveal(path, decryption.as_ref(), max_size))
+                .unwrap_or_else(|e| fail_payload("failed to reveal data", e));
+
+            if let Some(out) = reveal_matches.get_one::<String>("out") {
+                match save_payload(payload, out) {
+                    Ok((written, envelope)) => report_saved(&written, &envelope),
+                    Err(e) => fail("failed to save data", e),
+                }
+                return;
+            }
+
+            match payload.into_text() {
+                Ok(message) => println!("{}", message),
+                Err(e) => fail_payload("failed to reveal data", e),
+            }
+        }
+        Some(("keygen", keygen_matches)) => {
+            let output = keygen_matches
+                .get_one::<String>("output")
+                .map(|s| Output::from_arg(s))
+                .expect("output has default value");
+
+            let public_key = if keygen_matches.get_flag("signing") {
+                signing_keygen(&output).map(|key| key.to_string())
+            } else {
+                keygen(&output).map(|recipient| recipient.to_string())
+            };
+            match public_key {
+                Ok(public_key) if output == Output::Stdout => {
+                    eprintln!("public key: {}", public_key)
+                }
+                Ok(public_key) => println!("public key: {}", public_key),
+                Err(e) => fail("failed to generate key", e),
+            }
+        }
+        Some(("sign", sign_matches)) => {
+            let path = sign_matches
+                .get_one::<String>("path")
+                .map(|s| s.as_str())
+                .expect("path is required");
+
+            let key_path = sign_matches
+                .get_one::<String>("key")
+                .map(|s| s.as_str())
+                .expect("key is required");
+
+            let scope = sign_matches
+                .get_one::<String>("scope")
+                .map(|s| s.as_str())
+                .expect("scope has default value");
+
+            let include = sign_matches
+                .get_many::<String>("include")
+                .unwrap_or_default()
+                .map(|chunk_type| ChunkType::from_str(chunk_type))
+                .collect::<Result<Vec<_>, _>>();
+
+            let output = output_from(sign_matches, path, "signed.png");
+
+            let result = read_signing_key(key_path).and_then(|key| {
+                let scope = SignatureScope {
+                    kind: ScopeKind::from_str(scope)?,
+                    include: include?,
+                };
+                sign(path, &key, scope, &output)
+            });
+            match result {
+                Ok(_) if output == Output::Stdout => eprintln!("png has been signed"),
+                Ok(_) => println!("png has been signed"),
+                Err(e) => fail("failed to sign png", e),
+            }
+        }
+        Some(("verify", verify_matches)) => {
+            let path = verify_matches
+                .get_one::<String>("path")
+                .map(|s| s.as_str())
+                .expect("path is required");
+
+            let key = verify_matches
+                .get_one::<String>("key")
+                .map(|key| VerifyingKey::from_str(key))
+                .transpose()
+                .unwrap_or_else(|e| fail("verification failed", e));
+            let result = verify(path, key.as_ref());
+            match result {
+                Ok(signatures) => {
+                    for signature in signatures {
+                        let include: Vec<String> = signature
+                            .scope()
+                            .include
+                            .iter()
+                            .map(|chunk_type| chunk_type.to_string())
+                            .collect();
+                        let scope = if include.is_empty() {
+                            signature.scope().kind.to_string()
+                        } else {
+                            format!("{} + {}", signature.scope().kind, include.join(", "))
+                        };
+                        match key {
+                            Some(_) => println!(
+                                "valid signature by {} over {} chunks",
+                                signature.key(),
+                                scope
+                            ),
+                            None => println!(
+                                "intact signature by unverified key {} over {} chunks",
+                                signature.key(),
+                                scope
+                            ),
+                        }
+                    }
+                    if key.is_none() {
+                        eprintln!(
+                            "note: only integrity was checked, anyone can re-sign an edited png. \
+                            Pass --key to check who signed it"
+                        );
+                    }
+                }
+                Err(e) => fail("verification failed", e),
             }
         }
         Some(("validate", validate_matches)) => {
@@ -96,9 +471,15 @@ fn main() {
                 .map(|s| s.as_str())
                 .expect("type is required");
 
+            // The breakdown shows which property bit makes a type invalid, so it
+            // is printed before the strict check.
+            let parsed = ChunkType::from_str(chunk_type)
+                .unwrap_or_else(|e| fail("chunk type is invalid", e));
+            println!("{}", describe_chunk_type(&parsed));
+
             match validate(chunk_type) {
                 Ok(_) => println!("chunk type is valid"),
-                Err(e) => println!("chunk type is invalid: {}", e),
+                Err(e) => fail("chunk type is invalid", e),
             }
         }
         Some(("chunks", chunks_matches)) => {
@@ -107,6 +488,28 @@ fn main() {
                 .map(|s| s.as_str())
                 .expect("path is required");
 
+            let format = chunks_matches
+                .get_one::<String>("format")
+                .map(|s| s.as_str());
+
+            if let Some(format) = format {
+                let encoding = chunks_matches
+                    .get_one::<String>("encoding")
+                    .map(|s| s.as_str())
+                    .or(chunks_matches.get_flag("with-data").then_some("utf8"));
+
+                let result = ListFormat::from_str(format).and_then(|format| {
+                    let encoding = encoding.map(DataEncoding::from_str).transpose()?;
+                    let infos = chunk_infos(path, encoding)?;
+                    Ok(format_chunk_infos(&infos, format))
+                });
+                match result {
+                    Ok(output) => println!("{}", output),
+                    Err(e) => fail("failed to get chunk list", e),
+                }
+                return;
+            }
+
             match get_chunks(path) {
                 Ok(chunks) => {
                     let print_data = chunks_matches.get_flag("with-data");
@@ -123,7 +526,40 @@ fn main() {
                         println!("{}", output);
                     }
                 }
-                Err(e) => println!("failed to get chunk list: {}", e),
+                Err(e) => fail("failed to get chunk list", e),
+            }
+        }
+        Some(("info", info_matches)) => {
+            let path = info_matches
+                .get_one::<String>("path")
+                .map(|s| s.as_str())
+                .expect("path is required");
+
+            match info(path) {
+                Ok(ihdr) => println!("{}", ihdr),
+                Err(e) => fail("failed to read image header", e),
+            }
+        }
+        Some(("lint", lint_matches)) => {
+            let path = lint_matches
+                .get_one::<String>("path")
+                .map(|s| s.as_str())
+                .expect("path is required");
+
+            match lint(path) {
+                Ok(violations) => {
+                    for violation in &violations {
+                        println!("{}", violation);
+                    }
+                    let errors = violations
+                        .iter()
+                        .filter(|violation| violation.severity == Severity::Error)
+                        .count();
+                    if errors > 0 {
+                        fail("lint failed", PngError::StructureViolations(errors));
+                    }
+                }
+                Err(e) => fail("failed to lint png", e),
             }
         }
         Some(("remove", remove_matches)) => {
@@ -132,18 +568,352 @@ fn main() {
                 .map(|s| s.as_str())
                 .expect("path is required");
 
-
             let chunk_type = remove_matches
                 .get_one::<String>("type")
                 .map(|s| s.as_str())
                 .expect("type is required");
 
-            if let Err(e) = remove_chunk(path, chunk_type) {
-                println!("failed to remove chunk: {}", e)
+            let output = output_from(
+                remove_matches,
+                path,
+                &format!("removed_chunk_{}.png", chunk_type),
+            );
+
+            let occurrence = match remove_matches.get_one::<usize>("index") {
+                Some(index) => Occurrence::Index(*index),
+                None if remove_matches.get_flag("all") => Occurrence::All,
+                None => Occurrence::First,
+            };
+
+            let result = remove_matches
+                .get_many::<String>("where")
+                .unwrap_or_default()
+                .map(|filter| ChunkFilter::from_str(filter))
+                .collect::<Result<Vec<_>, _>>()
+                .and_then(|filters| remove_chunks(path, chunk_type, occurrence, &filters, &output));
+            let message = match result {
+                Ok(1) => String::from("1 chunk has been removed"),
+                Ok(count) => format!("{} chunks have been removed", count),
+                Err(e) => fail("failed to remove chunk", e),
+            };
+            if output == Output::Stdout {
+                eprintln!("{}", message)
             } else {
-                println!("chunk has been removed")
+                println!("{}", message)
+            }
+        }
+        Some(("strip", strip_matches)) => {
+            let path = strip_matches
+                .get_one::<String>("path")
+                .map(|s| s.as_str())
+                .expect("path is required");
+
+            let output = output_from(strip_matches, path, "stripped.png");
+
+            let preset = match strip_matches.get_flag("all-ancillary") {
+                true => StripPreset::AllAncillary,
+                false => StripPreset::Privacy,
+            };
+
+            let result = strip_matches
+                .get_many::<String>("keep")
+                .unwrap_or_default()
+                .map(|chunk_type| ChunkType::from_str(chunk_type))
+                .collect::<Result<Vec<_>, _>>()
+                .and_then(|keep| strip_metadata(path, preset, &keep, &output));
+            let stripped = match result {
+                Ok(stripped) => stripped,
+                Err(e) => fail("failed to strip png", e),
+            };
+
+            let bytes: u64 = stripped.iter().map(|s| s.chunk.length() as u64).sum();
+            let mut report: Vec<String> =
+                stripped.iter().map(|s| format!("dropped {}", s)).collect();
+            report.push(match stripped.len() {
+                0 => String::from("nothing to strip"),
+                1 => format!("1 chunk has been stripped ({} bytes)", bytes),
+                count => format!("{} chunks have been stripped ({} bytes)", count, bytes),
+            });
+            for line in report {
+                if output == Output::Stdout {
+                    eprintln!("{}", line)
+                } else {
+                    println!("{}", line)
+                }
             }
         }
+        Some(("text", text_matches)) => run_text(text_matches),
         _ => panic!("oh shieet"),
     }
 }
+
+fn run_text(matches: &ArgMatches) {
+    let (name, matches) = matches.subcommand().expect("subcommand is required");
+    let path = matches
+        .get_one::<String>("path")
+        .map(|s| s.as_str())
+        .expect("path is required");
+    let keyword = || {
+        matches
+            .get_one::<String>("keyword")
+            .map(|s| s.as_str())
+            .expect("keyword is required")
+    };
+
+    match name {
+        "list" => match text_list(path) {
+            Ok(texts) => {
+                for text in texts {
+                    println!("{}: {}", text.keyword(), text.text());
+                }
+            }
+            Err(e) => fail("failed to list text chunks", e),
+        },
+        "get" => match text_get(path, keyword()) {
+            Ok(text) => println!("{}", text),
+            Err(e) => fail("failed to get text", e),
+        },
+        "set" => {
+            let text = matches
+                .get_one::<String>("text")
+                .map(|s| s.as_str())
+                .expect("text is required");
+            let output = output_from(matches, path, "text.png");
+
+            if let Err(e) = text_set(path, keyword(), text, &output) {
+                fail("failed to set text", e)
+            }
+        }
+        "delete" => {
+            let output = output_from(matches, path, "text.png");
+            match text_delete(path, keyword(), &output) {
+                Ok(removed) if output == Output::Stdout => {
+                    eprintln!("{} text chunks have been removed", removed)
+                }
+                Ok(removed) => println!("{} text chunks have been removed", removed),
+                Err(e) => fail("failed to delete text", e),
+            }
+        }
+        _ => unreachable!("unknown text subcommand"),
+    }
+}
+
+/// Data, encryption and compression arguments shared by encode and hide.
+fn payload_args() -> [Arg; 7] {
+    [
+        Arg::new("data").required(false).help("data to be encoded"),
+        Arg::new("file")
+            .long("file")
+            .short('f')
+            .conflicts_with("data")
+            .help("embeds raw bytes of the file, along with its name, MIME type and size"),
+        Arg::new("raw")
+            .long("raw")
+            .action(ArgAction::SetTrue)
+            .requires("file")
+            .help("embeds only the bytes of --file, without name, MIME type and size"),
+        Arg::new("encrypt")
+            .long("encrypt")
+            .help("encrypts data with a key derived from the passphrase")
+            .action(ArgAction::SetTrue)
+            .requires("passphrase"),
+        passphrase_arg(),
+        Arg::new("recipient")
+            .long("recipient")
+            .short('r')
+            .action(ArgAction::Append)
+            .conflicts_with("encrypt")
+            .help("encrypts data to the public key printed by keygen, may be repeated"),
+        Arg::new("compress")
+            .long("compress")
+            .value_parser(["zlib", "zstd", "brotli"])
+            .help("compresses data before encrypting and storing it"),
+    ]
+}
+
+/// Decryption and output arguments shared by decode and reveal.
+fn decryption_args() -> [Arg; 5] {
+    [
+        Arg::new("decrypt")
+            .long("decrypt")
+            .help("decrypts data encoded with --encrypt")
+            .action(ArgAction::SetTrue)
+            .requires("passphrase"),
+        passphrase_arg(),
+        Arg::new("identity")
+            .long("identity")
+            .short('i')
+            .conflicts_with("decrypt")
+            .help("key file written by keygen, decrypts data encoded with --recipient"),
+        Arg::new("out")
+            .long("out")
+            .help("writes raw bytes to the path, a directory restores the embedded file name, - writes to stdout"),
+        Arg::new("max-size")
+            .long("max-size")
+            .value_name("BYTES")
+            .value_parser(value_parser!(usize))
+            .default_value("67108864")
+            .help("refuses compressed data that decompresses to more bytes"),
+    ]
+}
+
+/// Bytes given as `data` or read with `--file`, exits if the file can't be read.
+fn payload_from(matches: &ArgMatches) -> Payload {
+    match matches.get_one::<String>("file") {
+        Some(file) => read_payload_file(file, matches.get_flag("raw"))
+            .unwrap_or_else(|e| fail("failed to read file", e)),
+        None => Payload::Message(
+            matches
+                .get_one::<String>("data")
+                .map(|s| s.as_bytes().to_vec())
+                .unwrap_or_default(),
+        ),
+    }
+}
+
+/// Codec requested with `--compress`, if any.
+fn compression_from(matches: &ArgMatches) -> Result<Option<Codec>, PngError> {
+    matches
+        .get_one::<String>("compress")
+        .map(|codec| Codec::from_str(codec))
+        .transpose()
+}
+
+/// Reports where `extract` or `save_payload` wrote the data.
+fn report_saved(written: &str, envelope: &FileEnvelope) {
+    let mut message = format!("{} bytes written to {}", envelope.data.len(), written);
+    if let Some(mime_type) = &envelope.mime_type {
+        message = format!("{} ({})", message, mime_type);
+    }
+    eprintln!("{}", message);
+}
+
+/// Encryption requested with `--encrypt` or `--recipient`, if any.
+fn encryption_from(matches: &ArgMatches) -> Result<Option<Encryption>, PngError> {
+    if matches.get_flag("encrypt") {
+        let passphrase = matches
+            .get_one::<String>("passphrase")
+            .expect("--encrypt requires passphrase");
+        return Ok(Some(Encryption::Passphrase(passphrase.clone())));
+    }
+
+    match matches.get_many::<String>("recipient") {
+        Some(recipients) => {
+            let recipients = recipients
+                .map(|recipient| Recipient::from_str(recipient))
+                .collect::<Result<_, _>>()?;
+            Ok(Some(Encryption::Recipients(recipients)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Decryption requested with `--decrypt` or `--identity`, if any.
+fn decryption_from(matches: &ArgMatches) -> Result<Option<Decryption>, PngError> {
+    if matches.get_flag("decrypt") {
+        let passphrase = matches
+            .get_one::<String>("passphrase")
+            .expect("--decrypt requires passphrase");
+        return Ok(Some(Decryption::Passphrase(passphrase.clone())));
+    }
+
+    matches
+        .get_one::<String>("identity")
+        .map(|path| read_identity(path).map(Decryption::Identity))
+        .transpose()
+}
+
+fn passphrase_arg() -> Arg {
+    Arg::new("passphrase")
+        .long("passphrase")
+        .env("PNGME_PASSPHRASE")
+        .hide_env_values(true)
+        .help("passphrase for --encrypt and --decrypt, prefer the environment variable")
+}
+
+fn output_args() -> [Arg; 2] {
+    [
+        Arg::new("output")
+            .short('o')
+            .long("output")
+            .help("path of the resulting png, - writes it to stdout. Without it a new file is created next to the input"),
+        Arg::new("in-place")
+            .long("in-place")
+            .help("atomically replaces the input png")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("output"),
+    ]
+}
+
+/// Picks output of a mutating subcommand, `default_name` is put next to the input
+/// file and never overwritten. Png read from stdin goes to stdout by default.
+fn output_from(matches: &ArgMatches, path: &str, default_name: &str) -> Output {
+    if matches.get_flag("in-place") {
+        return Output::InPlace;
+    }
+
+    match matches.get_one::<String>("output") {
+        Some(output) => Output::from_arg(output),
+        None if path == STDIN_PATH => Output::Stdout,
+        None => Output::NewPath(Path::new(path).with_file_name(default_name)),
+    }
+}
+
+fn exit_code(err: &PngError) -> i32 {
+    match err {
+        PngError::InvalidSignature => 3,
+        PngError::Truncated { .. } => 4,
+        PngError::CrcMismatch { .. } => 5,
+        PngError::InvalidChunkType(_) => 6,
+        PngError::ChunkNotFound(_) => 7,
+        PngError::InvalidUtf8(_) => 8,
+        PngError::TrailingData { .. } => 9,
+        PngError::ChunkTooLarge { .. } => 10,
+        PngError::IndexOutOfRange { .. } => 11,
+        PngError::InvalidPosition(_) => 12,
+        PngError::InvalidOutput(_) => 13,
+        PngError::InvalidFormat(_) => 14,
+        PngError::InvalidIhdr(_) => 15,
+        PngError::StructureViolations(_) => 16,
+        PngError::InvalidText(_) => 17,
+        PngError::KeywordNotFound(_) => 18,
+        PngError::Decompression(_) => 19,
+        PngError::InvalidEnvelope(_) => 20,
+        PngError::AuthenticationFailed => 21,
+        PngError::InvalidKey(_) => 22,
+        PngError::InvalidSignatureChunk(_) => 23,
+        PngError::SignatureMismatch => 24,
+        PngError::InvalidPayload(_) => 25,
+        PngError::UnsupportedImage(_) => 26,
+        PngError::InvalidImageData(_) => 27,
+        PngError::CapacityExceeded { .. } => 28,
+        // 2 is taken by clap for usage errors.
+        PngError::Io { .. } => 29,
+        PngError::BinaryFile(_) => 30,
+        PngError::EncryptedPayload => 31,
+    }
+}
+
+/// Like `fail`, but points at the flag that reads a payload `decode` and
+/// `reveal` can't print.
+fn fail_payload(context: &str, err: PngError) -> ! {
+    match err {
+        PngError::BinaryFile(_) => fail(&format!("{}, save it with --out", context), err),
+        PngError::EncryptedPayload => fail(
+            &format!("{}, decrypt it with --decrypt or --identity", context),
+            err,
+        ),
+        _ => fail(context, err),
+    }
+}
+
+fn fail(context: &str, err: PngError) -> ! {
+    let mut message = format!("{}: {}", context, err);
+    let mut source = err.source();
+    while let Some(e) = source {
+        message = format!("{}: {}", message, e);
+        source = e.source();
+    }
+    eprintln!("{}", message);
+    process::exit(exit_code(&err))
+}
@@ -0,0 +1,668 @@
+use std::{borrow::Cow, path::Path};
+
+use crate::{
+    chunk::{read_array, CRC_32_ISO},
+    compression::{self, Codec},
+    crypto::fill_random,
+    error::PngError,
+};
+
+/// Marks chunk data holding a file envelope.
+const MAGIC: &[u8; 4] = b"PMEf";
+
+/// Version of the envelope layout, bumped on incompatible changes.
+pub const FILE_ENVELOPE_VERSION: u8 = 1;
+
+/// Marks chunk data holding a fragment of a split payload.
+const FRAGMENT_MAGIC: &[u8; 4] = b"PMEs";
+
+/// Version of the fragment layout, bumped on incompatible changes.
+pub const FRAGMENT_VERSION: u8 = 1;
+
+/// Magic, version, payload id, index, total and checksum.
+pub const FRAGMENT_HEADER_LENGTH: usize = 4 + 1 + 4 + 4 + 4 + 4;
+
+/// Marks data written by `encode` and `hide`, so messages that happen to start
+/// with another magic are never mistaken for it.
+const PAYLOAD_MAGIC: &[u8; 4] = b"PMEp";
+
+/// Version of the payload layout, bumped on incompatible changes.
+pub const PAYLOAD_VERSION: u8 = 1;
+
+/// Magic, version, flags, codec and size of the body once decompressed.
+const PAYLOAD_HEADER_LENGTH: usize = 4 + 1 + 1 + 1 + 8;
+
+/// Flag set when the body is a `FileEnvelope` rather than a message.
+const FLAG_FILE: u8 = 1;
+
+/// Compressed payloads larger than this once decompressed are refused by default.
+pub const DEFAULT_MAX_DECOMPRESSED_LENGTH: usize = 64 * 1024 * 1024;
+
+/// Payloads longer than this are split by default. Decoders commonly refuse
+/// chunks of several megabytes, e.g. libpng's default limit is 8 MB.
+pub const DEFAULT_MAX_CHUNK_LENGTH: usize = 1024 * 1024;
+
+/// Raw file contents along with what's needed to restore the file exactly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEnvelope {
+    /// Original file name, without directories.
+    pub file_name: Option<String>,
+    pub mime_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+impl FileEnvelope {
+    /// Wraps `data` read from `path`, guessing the MIME type from its extension.
+    pub fn from_file(path: &Path, data: Vec<u8>) -> FileEnvelope {
+        FileEnvelope {
+            file_name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned()),
+            mime_type: guess_mime_type(path).map(String::from),
+            data,
+        }
+    }
+
+    /// Layout: magic, version, file name and MIME type each prefixed with their
+    /// u16 length (0 when missing), u64 size of data and the data itself.
+    /// Fails when the name or MIME type doesn't fit its length.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, PngError> {
+        let field = |name: &str, value: &Option<String>| -> Result<Vec<u8>, PngError> {
+            let bytes = value.as_deref().unwrap_or_default().as_bytes();
+            let length = u16::try_from(bytes.len()).map_err(|_| {
+                PngError::InvalidPayload(format!(
+                    "{} is {} bytes long, at most {} fit in a file envelope",
+                    name,
+                    bytes.len(),
+                    u16::MAX
+                ))
+            })?;
+            Ok(length
+                .to_be_bytes()
+                .into_iter()
+                .chain(bytes.iter().copied())
+                .collect())
+        };
+
+        Ok(MAGIC
+            .iter()
+            .copied()
+            .chain([FILE_ENVELOPE_VERSION])
+            .chain(field("file name", &self.file_name)?)
+            .chain(field("MIME type", &self.mime_type)?)
+            .chain((self.data.len() as u64).to_be_bytes())
+            .chain(self.data.iter().copied())
+            .collect())
+    }
+
+    /// Whether `data` starts like a file envelope.
+    pub fn is_file_envelope(data: &[u8]) -> bool {
+        data.starts_with(MAGIC)
+    }
+}
+
+impl TryFrom<&[u8]> for FileEnvelope {
+    type Error = PngError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let truncated = || PngError::InvalidPayload(String::from("file envelope is truncated"));
+
+        if !FileEnvelope::is_file_envelope(value) {
+            return Err(PngError::InvalidPayload(String::from(
+                "data is not a file envelope",
+            )));
+        }
+        let version = *value.get(MAGIC.len()).ok_or_else(truncated)?;
+        if version != FILE_ENVELOPE_VERSION {
+            return Err(PngError::InvalidPayload(format!(
+                "unsupported file envelope version {}",
+                version
+            )));
+        }
+
+        let mut offset = MAGIC.len() + 1;
+        let mut field = || -> Result<Option<String>, PngError> {
+            let length = u16::from_be_bytes(read_array(value, offset).map_err(|_| truncated())?);
+            let start = offset + 2;
+            let bytes = value
+                .get(start..start + length as usize)
+                .ok_or_else(truncated)?;
+            offset = start + length as usize;
+            Ok(match length {
+                0 => None,
+                _ => Some(String::from_utf8(bytes.to_vec())?),
+            })
+        };
+        let file_name = field()?;
+        let mime_type = field()?;
+
+        let size = u64::from_be_bytes(read_array(value, offset).map_err(|_| truncated())?);
+        let data = &value[offset + 8..];
+        if data.len() as u64 != size {
+            return Err(PngError::InvalidPayload(format!(
+                "file envelope declares {} bytes, holds {}",
+                size,
+                data.len()
+            )));
+        }
+
+        Ok(FileEnvelope {
+            file_name,
+            mime_type,
+            data: data.to_vec(),
+        })
+    }
+}
+
+/// Part of a payload too large for a single chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fragment {
+    /// Random id shared by every fragment of a payload.
+    pub payload_id: u32,
+    /// Position of this fragment, counted from 0.
+    pub index: u32,
+    pub total: u32,
+    /// CRC-32 of the whole reassembled payload.
+    pub checksum: u32,
+    pub data: Vec<u8>,
+}
+
+impl Fragment {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        FRAGMENT_MAGIC
+            .iter()
+            .copied()
+            .chain([FRAGMENT_VERSION])
+            .chain(self.payload_id.to_be_bytes())
+            .chain(self.index.to_be_bytes())
+            .chain(self.total.to_be_bytes())
+            .chain(self.checksum.to_be_bytes())
+            .chain(self.data.iter().copied())
+            .collect()
+    }
+
+    /// Whether `data` starts like a fragment.
+    pub fn is_fragment(data: &[u8]) -> bool {
+        data.starts_with(FRAGMENT_MAGIC)
+    }
+}
+
+impl TryFrom<&[u8]> for Fragment {
+    type Error = PngError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let truncated = || PngError::InvalidPayload(String::from("fragment is truncated"));
+
+        if !Fragment::is_fragment(value) {
+            return Err(PngError::InvalidPayload(String::from(
+                "data is not a fragment",
+            )));
+        }
+        let version = *value.get(FRAGMENT_MAGIC.len()).ok_or_else(truncated)?;
+        if version != FRAGMENT_VERSION {
+            return Err(PngError::InvalidPayload(format!(
+                "unsupported fragment version {}",
+                version
+            )));
+        }
+
+        let field = |offset: usize| -> Result<u32, PngError> {
+            Ok(u32::from_be_bytes(
+                read_array(value, offset).map_err(|_| truncated())?,
+            ))
+        };
+        let fragment = Fragment {
+            payload_id: field(5)?,
+            index: field(9)?,
+            total: field(13)?,
+            checksum: field(17)?,
+            data: value[FRAGMENT_HEADER_LENGTH..].to_vec(),
+        };
+        if fragment.index >= fragment.total {
+            return Err(PngError::InvalidPayload(format!(
+                "fragment index {} is out of range, payload has {} fragments",
+                fragment.index, fragment.total
+            )));
+        }
+        Ok(fragment)
+    }
+}
+
+/// Splits `payload` into fragments of at most `max_length` bytes, header included.
+pub fn split_payload(payload: &[u8], max_length: usize) -> Result<Vec<Fragment>, PngError> {
+    if max_length <= FRAGMENT_HEADER_LENGTH {
+        return Err(PngError::InvalidPayload(format!(
+            "chunks must hold more than the {} bytes of fragment header",
+            FRAGMENT_HEADER_LENGTH
+        )));
+    }
+
+    let parts: Vec<&[u8]> = payload
+        .chunks(max_length - FRAGMENT_HEADER_LENGTH)
+        .collect();
+    let total = u32::try_from(parts.len()).map_err(|_| {
+        PngError::InvalidPayload(format!("payload would need {} fragments", parts.len()))
+    })?;
+
+    let mut payload_id = [0; 4];
+    fill_random(&mut payload_id)?;
+    let checksum = CRC_32_ISO.checksum(payload);
+
+    Ok(parts
+        .into_iter()
+        .enumerate()
+        .map(|(index, part)| Fragment {
+            payload_id: u32::from_be_bytes(payload_id),
+            index: index as u32,
+            total,
+            checksum,
+            data: part.to_vec(),
+        })
+        .collect())
+}
+
+/// Joins fragments of a single payload, in any order. Fails on missing or
+/// duplicate fragments and when the result doesn't match the checksum.
+pub fn reassemble(mut fragments: Vec<Fragment>) -> Result<Vec<u8>, PngError> {
+    let first = fragments
+        .first()
+        .ok_or(PngError::InvalidPayload(String::from("no fragments")))?;
+    let (payload_id, total, checksum) = (first.payload_id, first.total, first.checksum);
+
+    if let Some(other) = fragments.iter().find(|fragment| {
+        fragment.payload_id != payload_id
+            || fragment.total != total
+            || fragment.checksum != checksum
+    }) {
+        return Err(PngError::InvalidPayload(format!(
+            "fragment {} doesn't belong to the payload of fragment {}",
+            other.index, fragments[0].index
+        )));
+    }
+
+    fragments.sort_by_key(|fragment| fragment.index);
+    let mut expected = 0;
+    for fragment in &fragments {
+        if fragment.index < expected {
+            return Err(PngError::InvalidPayload(format!(
+                "fragment {} appears more than once",
+                fragment.index
+            )));
+        }
+        if fragment.index > expected {
+            return Err(PngError::InvalidPayload(format!(
+                "fragment {} of {} is missing",
+                expected, total
+            )));
+        }
+        expected += 1;
+    }
+    if expected != total {
+        return Err(PngError::InvalidPayload(format!(
+            "fragment {} of {} is missing",
+            expected, total
+        )));
+    }
+
+    let payload: Vec<u8> = fragments
+        .into_iter()
+        .flat_map(|fragment| fragment.data)
+        .collect();
+    if CRC_32_ISO.checksum(&payload) != checksum {
+        return Err(PngError::InvalidPayload(String::from(
+            "reassembled payload doesn't match its checksum",
+        )));
+    }
+    Ok(payload)
+}
+
+/// Data embedded by `encode` or `hide`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Payload {
+    Message(Vec<u8>),
+    File(FileEnvelope),
+}
+
+impl Payload {
+    /// Layout: magic, version, flags, codec (0 when uncompressed), u64 size of
+    /// the body once decompressed and the body, compressed with `codec` if given.
+    pub fn to_bytes(&self, codec: Option<Codec>) -> Result<Vec<u8>, PngError> {
+        let (flags, body) = match self {
+            Payload::Message(data) => (0, Cow::Borrowed(data.as_slice())),
+            Payload::File(envelope) => (FLAG_FILE, Cow::Owned(envelope.to_bytes()?)),
+        };
+        let size = body.len() as u64;
+        let body = match codec {
+            Some(codec) => compression::compress(&body, codec),
+            None => body.into_owned(),
+        };
+
+        Ok(PAYLOAD_MAGIC
+            .iter()
+            .copied()
+            .chain([PAYLOAD_VERSION, flags, codec.map_or(0, |codec| codec as u8)])
+            .chain(size.to_be_bytes())
+            .chain(body)
+            .collect())
+    }
+
+    /// Parses data written by `to_bytes`, refusing bodies that decompress to
+    /// more than `max_length` bytes.
+    pub fn from_bytes(data: &[u8], max_length: usize) -> Result<Payload, PngError> {
+        if !Payload::is_payload(data) {
+            return Err(PngError::InvalidPayload(String::from(
+                "data has no payload header",
+            )));
+        }
+        if data.len() < PAYLOAD_HEADER_LENGTH {
+            return Err(PngError::InvalidPayload(String::from(
+                "payload is truncated",
+            )));
+        }
+        let version = data[PAYLOAD_MAGIC.len()];
+        if version != PAYLOAD_VERSION {
+            return Err(PngError::InvalidPayload(format!(
+                "unsupported payload version {}",
+                version
+            )));
+        }
+        let flags = data[PAYLOAD_MAGIC.len() + 1];
+        if flags & !FLAG_FILE != 0 {
+            return Err(PngError::InvalidPayload(format!(
+                "unknown payload flags {:#04x}",
+                flags
+            )));
+        }
+        let codec = match data[PAYLOAD_MAGIC.len() + 2] {
+            0 => None,
+            codec => Some(Codec::try_from(codec)?),
+        };
+        let size = u64::from_be_bytes(read_array(data, PAYLOAD_MAGIC.len() + 3)?);
+        let stored = &data[PAYLOAD_HEADER_LENGTH..];
+
+        let body = match codec {
+            None => stored.to_vec(),
+            // The declared size only lets oversized payloads fail early, the
+            // limit is still enforced while decompressing.
+            Some(_) if size > max_length as u64 => {
+                return Err(PngError::Decompression(format!(
+                    "payload decompresses to {} bytes, more than the limit of {}",
+                    size, max_length
+                )));
+            }
+            Some(codec) => compression::decompress(stored, codec, size as usize)?,
+        };
+        if body.len() as u64 != size {
+            return Err(PngError::InvalidPayload(format!(
+                "payload declares {} bytes, holds {}",
+                size,
+                body.len()
+            )));
+        }
+
+        match flags & FLAG_FILE {
+            0 => Ok(Payload::Message(body)),
+            _ => Ok(Payload::File(FileEnvelope::try_from(body.as_slice())?)),
+        }
+    }
+
+    /// Whether `data` starts with a payload header.
+    pub fn is_payload(data: &[u8]) -> bool {
+        data.starts_with(PAYLOAD_MAGIC)
+    }
+
+    /// Message as UTF-8 text. A file is returned as text when it is valid
+    /// UTF-8, otherwise `BinaryFile` tells it apart from a broken message.
+    pub fn into_text(self) -> Result<String, PngError> {
+        match self {
+            Payload::Message(data) => Ok(String::from_utf8(data)?),
+            Payload::File(envelope) => String::from_utf8(envelope.data)
+                .map_err(|_| PngError::BinaryFile(envelope.file_name)),
+        }
+    }
+
+    /// File to save, a message becomes a file without name or MIME type.
+    pub fn into_file(self) -> FileEnvelope {
+        match self {
+            Payload::Message(data) => FileEnvelope {
+                file_name: None,
+                mime_type: None,
+                data,
+            },
+            Payload::File(envelope) => envelope,
+        }
+    }
+}
+
+/// MIME type of common file types, `None` when the extension is unknown.
+pub fn guess_mime_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    let mime_type = match extension.as_str() {
+        "txt" => "text/plain",
+        "md" => "text/markdown",
+        "html" | "htm" => "text/html",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        _ => return None,
+    };
+    Some(mime_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_envelope_roundtrip() {
+        let envelope = FileEnvelope::from_file(Path::new("/tmp/archive.ZIP"), vec![0, 1, 255]);
+        assert_eq!(envelope.file_name.as_deref(), Some("archive.ZIP"));
+        assert_eq!(envelope.mime_type.as_deref(), Some("application/zip"));
+
+        let bytes = envelope.to_bytes().unwrap();
+        assert!(FileEnvelope::is_file_envelope(&bytes));
+        assert_eq!(FileEnvelope::try_from(bytes.as_slice()).unwrap(), envelope);
+    }
+
+    #[test]
+    fn test_file_envelope_without_metadata() {
+        let envelope = FileEnvelope {
+            file_name: None,
+            mime_type: None,
+            data: Vec::new(),
+        };
+        let bytes = envelope.to_bytes().unwrap();
+        assert_eq!(bytes.len(), 4 + 1 + 2 + 2 + 8);
+        assert_eq!(FileEnvelope::try_from(bytes.as_slice()).unwrap(), envelope);
+    }
+
+    #[test]
+    fn test_file_envelope_name_too_long() {
+        let envelope = FileEnvelope {
+            file_name: Some("a".repeat(u16::MAX as usize + 1)),
+            mime_type: None,
+            data: Vec::new(),
+        };
+        assert!(matches!(
+            envelope.to_bytes(),
+            Err(PngError::InvalidPayload(_))
+        ));
+        assert!(Payload::File(envelope).to_bytes(None).is_err());
+    }
+
+    #[test]
+    fn test_invalid_file_envelope() {
+        let bytes = FileEnvelope::from_file(Path::new("key.bin"), vec![7; 10])
+            .to_bytes()
+            .unwrap();
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = 2;
+        let mut wrong_size = bytes.clone();
+        wrong_size.pop();
+
+        for data in [&b"PME"[..], &bytes[..8], &wrong_version, &wrong_size] {
+            assert!(
+                FileEnvelope::try_from(data).is_err(),
+                "{:?} should be invalid",
+                data
+            );
+        }
+    }
+
+    #[test]
+    fn test_split_and_reassemble() {
+        let payload: Vec<u8> = (0..1000).map(|i| (i % 251) as u8).collect();
+        let fragments = split_payload(&payload, 121).unwrap();
+
+        assert_eq!(fragments.len(), 10);
+        assert!(fragments
+            .iter()
+            .all(|fragment| fragment.to_bytes().len() <= 121));
+
+        let mut shuffled: Vec<Fragment> = fragments
+            .iter()
+            .map(|fragment| Fragment::try_from(fragment.to_bytes().as_slice()).unwrap())
+            .collect();
+        shuffled.reverse();
+        assert_eq!(reassemble(shuffled).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_missing_and_duplicate_fragments() {
+        let fragments = split_payload(&[7; 100], 50).unwrap();
+        assert_eq!(fragments.len(), 4);
+
+        let mut missing = fragments.clone();
+        missing.remove(2);
+        let mut missing_last = fragments.clone();
+        missing_last.pop();
+        let mut duplicate = fragments.clone();
+        duplicate.push(fragments[1].clone());
+        let mut corrupted = fragments.clone();
+        corrupted[3].data[0] = 8;
+        let mut foreign = fragments.clone();
+        foreign[0] = split_payload(&[7; 100], 50).unwrap().remove(0);
+
+        for fragments in [
+            missing,
+            missing_last,
+            duplicate,
+            corrupted,
+            foreign,
+            Vec::new(),
+        ] {
+            assert!(matches!(
+                reassemble(fragments),
+                Err(PngError::InvalidPayload(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_invalid_fragment() {
+        let bytes = split_payload(b"data", 64).unwrap()[0].to_bytes();
+
+        let mut out_of_range = bytes.clone();
+        out_of_range[12] = 1;
+        assert!(Fragment::try_from(out_of_range.as_slice()).is_err());
+        assert!(Fragment::try_from(&bytes[..20]).is_err());
+        assert!(split_payload(b"data", FRAGMENT_HEADER_LENGTH).is_err());
+    }
+
+    #[test]
+    fn test_payload_roundtrip() {
+        let message = br#"{"message": "This is where your secret message will be!"}"#.repeat(20);
+        let file = FileEnvelope::from_file(Path::new("notes.txt"), message.clone());
+        for payload in [Payload::Message(message.clone()), Payload::File(file)] {
+            let bytes = payload.to_bytes(None).unwrap();
+            assert!(Payload::is_payload(&bytes));
+            assert_eq!(Payload::from_bytes(&bytes, 0).unwrap(), payload);
+
+            for codec in [Codec::Zlib, Codec::Zstd, Codec::Brotli] {
+                let compressed = payload.to_bytes(Some(codec)).unwrap();
+                assert!(compressed.len() < message.len());
+                assert_eq!(
+                    Payload::from_bytes(&compressed, 2 * message.len()).unwrap(),
+                    payload
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_payload_decompression_limit() {
+        let compressed = Payload::Message(vec![0; 4096])
+            .to_bytes(Some(Codec::Zstd))
+            .unwrap();
+        assert!(matches!(
+            Payload::from_bytes(&compressed, 4095),
+            Err(PngError::Decompression(_))
+        ));
+
+        // A forged size can't get past the limit either.
+        let mut forged = compressed.clone();
+        forged[7..15].copy_from_slice(&16u64.to_be_bytes());
+        assert!(Payload::from_bytes(&forged, 4095).is_err());
+    }
+
+    #[test]
+    fn test_invalid_payload() {
+        let bytes = Payload::Message(b"hello".to_vec())
+            .to_bytes(Some(Codec::Zlib))
+            .unwrap();
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = 2;
+        let mut unknown_flags = bytes.clone();
+        unknown_flags[5] = 0x80;
+        let mut unknown_codec = bytes.clone();
+        unknown_codec[6] = 9;
+        let mut wrong_size = Payload::Message(b"hello".to_vec()).to_bytes(None).unwrap();
+        wrong_size.pop();
+
+        for data in [
+            &b"hello"[..],
+            &bytes[..10],
+            &wrong_version,
+            &unknown_flags,
+            &unknown_codec,
+            &wrong_size,
+        ] {
+            assert!(
+                Payload::from_bytes(data, 4096).is_err(),
+                "{:?} should be invalid",
+                data
+            );
+        }
+    }
+
+    #[test]
+    fn test_payload_into_text() {
+        assert_eq!(
+            Payload::Message(b"hello".to_vec()).into_text().unwrap(),
+            "hello"
+        );
+        let binary = FileEnvelope::from_file(Path::new("keys.zip"), vec![0xff]);
+        assert!(matches!(
+            Payload::File(binary).into_text(),
+            Err(PngError::BinaryFile(Some(name))) if name == "keys.zip"
+        ));
+    }
+
+    #[test]
+    fn test_guess_mime_type() {
+        assert_eq!(guess_mime_type(Path::new("a.png")), Some("image/png"));
+        assert_eq!(guess_mime_type(Path::new("a.unknown")), None);
+        assert_eq!(guess_mime_type(Path::new("Makefile")), None);
+    }
+}
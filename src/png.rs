@@ -1,46 +1,59 @@
 use std::{
     fmt::Display,
-    io::{BufReader, Read},
+    io::{Read, Write},
     str::FromStr,
 };
 
-use crate::{chunk::Chunk, chunk_type::ChunkType};
+use crate::{
+    chunk::{read_array, Chunk, MAX_CHUNK_LENGTH},
+    chunk_type::ChunkType,
+    error::PngError,
+    ihdr::Ihdr,
+    image::{decode_image, decode_image_passes, encode_image, EncodeOptions, RawImage},
+    lint::{validate_chunks, Violation},
+    reader::ChunkReader,
+    writer::ChunkWriter,
+};
 
 pub struct Png {
     chunks: Vec<Chunk>,
 }
 
-impl TryFrom<&[u8]> for Png {
-    type Error = String;
+/// Where `Png::insert_chunk` puts a new chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkPosition {
+    /// Right before `IEND`, or at the end if there is no `IEND`.
+    #[default]
+    BeforeIend,
+    AfterIhdr,
+    BeforeFirstIdat,
+    Index(usize),
+    /// After every other chunk, including `IEND`.
+    End,
+}
 
-    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        let mut reader = BufReader::new(value);
-        let mut header_buf: [u8; 8] = Default::default();
-        reader
-            .read_exact(&mut header_buf)
-            .or(Err("read header bytes"))?;
-        if &header_buf != Self::STANDARD_HEADER {
-            return Err(String::from("invalid png header"));
+impl FromStr for ChunkPosition {
+    type Err = PngError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "before-iend" => Ok(ChunkPosition::BeforeIend),
+            "after-ihdr" => Ok(ChunkPosition::AfterIhdr),
+            "before-idat" => Ok(ChunkPosition::BeforeFirstIdat),
+            "end" => Ok(ChunkPosition::End),
+            index => index
+                .parse()
+                .map(ChunkPosition::Index)
+                .or(Err(PngError::InvalidPosition(index.to_string()))),
         }
+    }
+}
 
-        let mut chunks = Vec::new();
-        let mut len_buf: [u8; 4] = Default::default();
-        while let Ok(()) = reader.read_exact(&mut len_buf) {
-            let length = u32::from_be_bytes(len_buf);
-            let remain_len = (length + 4 + 4) as usize;
-            let mut chunk_remainging_bytes = vec![0u8; remain_len];
-
-            reader
-                .read_exact(&mut chunk_remainging_bytes)
-                .or(Err(String::from("reading remain chunk bytes")))?;
-
-            let r: &[u8] = chunk_remainging_bytes.as_ref();
-            let data: Vec<u8> = len_buf.iter().chain(r.iter()).copied().collect();
-
-            chunks.push(Chunk::try_from(data.as_ref())?);
-        }
+impl TryFrom<&[u8]> for Png {
+    type Error = PngError;
 
-        Ok(Png { chunks })
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        Png::try_from_with_max_length(value, MAX_CHUNK_LENGTH)
     }
 }
 
@@ -54,29 +67,158 @@ impl Display for Png {
 }
 
 impl Png {
-    const STANDARD_HEADER: &[u8; 8] = &[137, 80, 78, 71, 13, 10, 26, 10];
+    pub(crate) const STANDARD_HEADER: &[u8; 8] = &[137, 80, 78, 71, 13, 10, 26, 10];
 
-    fn from_chunks(chunks: Vec<Chunk>) -> Png {
+    pub fn from_chunks(chunks: Vec<Chunk>) -> Png {
         Png { chunks }
     }
 
+    /// Reads a whole png from `reader` chunk by chunk, without buffering the raw file.
+    pub fn read_from<R: Read>(reader: R) -> Result<Png, PngError> {
+        let chunks = ChunkReader::new(reader)?.collect::<Result<_, _>>()?;
+        Ok(Png { chunks })
+    }
+
+    /// Parses a png, rejecting any chunk whose data is longer than `max_length`.
+    /// Every declared length is checked against the input before anything is allocated.
+    pub fn try_from_with_max_length(value: &[u8], max_length: u32) -> Result<Png, PngError> {
+        let header_buf: [u8; 8] = read_array(value, 0).or(Err(PngError::InvalidSignature))?;
+        if &header_buf != Self::STANDARD_HEADER {
+            return Err(PngError::InvalidSignature);
+        }
+
+        let mut chunks = Vec::new();
+        let mut offset = Self::STANDARD_HEADER.len();
+        while offset < value.len() {
+            let length = u32::from_be_bytes(read_array(value, offset)?);
+            if length > max_length {
+                return Err(PngError::ChunkTooLarge {
+                    length,
+                    max: max_length,
+                    offset,
+                });
+            }
+
+            let chunk_end = (length as usize)
+                .checked_add(12)
+                .and_then(|chunk_len| offset.checked_add(chunk_len))
+                .filter(|chunk_end| *chunk_end <= value.len())
+                .ok_or(PngError::Truncated {
+                    offset: value.len(),
+                })?;
+
+            let chunk = Chunk::try_from_with_max_length(&value[offset..chunk_end], max_length)
+                .map_err(|e| e.at_offset(offset))?;
+            chunks.push(chunk);
+            offset = chunk_end;
+        }
+
+        Ok(Png { chunks })
+    }
+
     pub fn append_chunk(&mut self, chunk: Chunk) {
         self.chunks.push(chunk);
     }
 
-    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk, String> {
+    /// Inserts `chunk` right before `IEND`, so strict decoders don't drop it.
+    pub fn insert_before_iend(&mut self, chunk: Chunk) {
+        let idx = self.position_of("IEND").unwrap_or(self.chunks.len());
+        self.chunks.insert(idx, chunk);
+    }
+
+    pub fn insert_chunk(&mut self, chunk: Chunk, position: ChunkPosition) -> Result<(), PngError> {
+        self.insert_chunks(vec![chunk], position)
+    }
+
+    /// Inserts `chunks` at `position`, keeping their order.
+    pub fn insert_chunks(
+        &mut self,
+        chunks: Vec<Chunk>,
+        position: ChunkPosition,
+    ) -> Result<(), PngError> {
+        let idx = match position {
+            ChunkPosition::BeforeIend => self.position_of("IEND").unwrap_or(self.chunks.len()),
+            ChunkPosition::AfterIhdr => {
+                self.position_of("IHDR")
+                    .ok_or(PngError::ChunkNotFound(String::from("IHDR")))?
+                    + 1
+            }
+            ChunkPosition::BeforeFirstIdat => self
+                .position_of("IDAT")
+                .ok_or(PngError::ChunkNotFound(String::from("IDAT")))?,
+            ChunkPosition::Index(idx) if idx <= self.chunks.len() => idx,
+            ChunkPosition::Index(idx) => {
+                return Err(PngError::IndexOutOfRange {
+                    index: idx,
+                    len: self.chunks.len(),
+                })
+            }
+            ChunkPosition::End => self.chunks.len(),
+        };
+
+        self.chunks.splice(idx..idx, chunks);
+        Ok(())
+    }
+
+    fn position_of(&self, chunk_type: &str) -> Option<usize> {
+        self.chunks
+            .iter()
+            .position(|chunk| chunk.chunk_type().bytes() == chunk_type.as_bytes())
+    }
+
+    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk, PngError> {
         let given_chunk_type = ChunkType::from_str(chunk_type)?;
 
         let pos_idx = self
             .chunks
             .iter()
             .position(|chunk| chunk.chunk_type() == &given_chunk_type)
-            .ok_or(String::from("chunk not found"))?;
+            .ok_or(PngError::ChunkNotFound(chunk_type.to_string()))?;
 
         Ok(self.chunks.remove(pos_idx))
     }
 
-    fn header(&self) -> &[u8; 8] {
+    /// Removes every `chunk_type` chunk and returns them, possibly none.
+    pub fn remove_all(&mut self, chunk_type: &str) -> Result<Vec<Chunk>, PngError> {
+        let given_chunk_type = ChunkType::from_str(chunk_type)?;
+        Ok(self.retain(|chunk| chunk.chunk_type() != &given_chunk_type))
+    }
+
+    /// Removes the chunk at `index` of `chunks()`.
+    pub fn remove_at(&mut self, index: usize) -> Result<Chunk, PngError> {
+        if index >= self.chunks.len() {
+            return Err(PngError::IndexOutOfRange {
+                index,
+                len: self.chunks.len(),
+            });
+        }
+        Ok(self.chunks.remove(index))
+    }
+
+    /// Keeps only chunks for which `keep` returns true and returns the removed
+    /// ones, in file order.
+    pub fn retain<F: FnMut(&Chunk) -> bool>(&mut self, mut keep: F) -> Vec<Chunk> {
+        let (kept, removed) = self.chunks.drain(..).partition(|chunk| keep(chunk));
+        self.chunks = kept;
+        removed
+    }
+
+    /// Parses the `IHDR` chunk, which must come first.
+    pub fn header_info(&self) -> Result<Ihdr, PngError> {
+        let first = self
+            .chunks
+            .first()
+            .filter(|chunk| &chunk.chunk_type().bytes() == b"IHDR")
+            .ok_or(PngError::ChunkNotFound(String::from("IHDR")))?;
+        Ihdr::try_from(first)
+    }
+
+    /// Checks chunk ordering and multiplicity rules of the PNG specification.
+    pub fn validate(&self) -> Vec<Violation> {
+        validate_chunks(&self.chunks)
+    }
+
+    pub fn header(&self) -> &[u8; 8] {
         Self::STANDARD_HEADER
     }
 
@@ -84,6 +226,18 @@ impl Png {
         self.chunks.iter().collect()
     }
 
+    /// Every chunk of `chunk_type`, in file order.
+    pub fn chunks_by_type(&self, chunk_type: &str) -> Vec<&Chunk> {
+        let Ok(given_chunk_type) = ChunkType::from_str(chunk_type) else {
+            return Vec::new();
+        };
+
+        self.chunks
+            .iter()
+            .filter(|chunk| chunk.chunk_type() == &given_chunk_type)
+            .collect()
+    }
+
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
         let Ok(given_chunk_type) = ChunkType::from_str(chunk_type) else {
             return None;
@@ -94,19 +248,80 @@ impl Png {
             .find(|chunk| chunk.chunk_type() == &given_chunk_type)
     }
 
-    pub fn as_bytes(&self) -> Vec<u8> {
-        let all_chunks_bytes: Vec<u8> = self
-            .chunks()
-            .iter()
-            .flat_map(|chunk| chunk.as_bytes())
-            .collect();
+    /// Decodes the pixels: inflates the `IDAT` stream, reverses the scanline
+    /// filters and de-interlaces Adam7 images.
+    pub fn image_data(&self) -> Result<RawImage, PngError> {
+        decode_image(&self.header_info()?, &self.image_stream())
+    }
 
-        Self::STANDARD_HEADER
-            .iter()
-            .chain(all_chunks_bytes.iter())
-            .copied()
+    /// Decodes the pixels of each of the seven Adam7 passes separately, some may
+    /// be empty for small images. A non-interlaced image has a single pass.
+    pub fn image_passes(&self) -> Result<Vec<RawImage>, PngError> {
+        decode_image_passes(&self.header_info()?, &self.image_stream())
+    }
+
+    /// Filters and deflates `image` into new `IDAT` chunks, which take the place
+    /// of the old ones. The image must match `IHDR` and is interlaced if it says so.
+    pub fn set_image_data(
+        &mut self,
+        image: RawImage,
+        options: EncodeOptions,
+    ) -> Result<(), PngError> {
+        let stream = encode_image(&self.header_info()?, &image, &options)?;
+        self.replace_image_stream(&stream, options.idat_length)
+    }
+
+    /// Data of every `IDAT` chunk joined into the compressed image stream.
+    pub fn image_stream(&self) -> Vec<u8> {
+        self.chunks_by_type("IDAT")
+            .into_iter()
+            .flat_map(|chunk| chunk.data().iter().copied())
             .collect()
     }
+
+    /// Replaces the `IDAT` chunks with `stream` split into chunks of at most
+    /// `chunk_length` bytes, where the first old `IDAT` chunk was.
+    pub(crate) fn replace_image_stream(
+        &mut self,
+        stream: &[u8],
+        chunk_length: usize,
+    ) -> Result<(), PngError> {
+        if chunk_length == 0 || chunk_length > MAX_CHUNK_LENGTH as usize {
+            return Err(PngError::InvalidOutput(format!(
+                "IDAT chunks can't hold {} bytes",
+                chunk_length
+            )));
+        }
+        let idx = self
+            .position_of("IDAT")
+            .ok_or(PngError::ChunkNotFound(String::from("IDAT")))?;
+        let idat = ChunkType::from_str("IDAT").expect("IDAT is a valid chunk type");
+
+        self.chunks.retain(|chunk| chunk.chunk_type() != &idat);
+        let chunks = stream
+            .chunks(chunk_length)
+            .map(|data| Chunk::new(idat.clone(), data.to_vec()))
+            .collect::<Vec<_>>();
+        self.chunks.splice(idx..idx, chunks);
+        Ok(())
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes)
+            .expect("writing to a Vec never fails");
+        bytes
+    }
+
+    /// Writes the signature and every chunk straight to `writer`.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), PngError> {
+        let mut chunk_writer = ChunkWriter::new(writer)?;
+        for chunk in &self.chunks {
+            chunk_writer.write_chunk(chunk)?;
+        }
+        chunk_writer.finish()?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -117,13 +332,11 @@ mod tests {
     use std::convert::TryFrom;
 
     fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::new();
-
-        chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
-        chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
-        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
-
-        chunks
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap(),
+            chunk_from_strings("LASt", "I am the last chunk").unwrap(),
+        ]
     }
 
     fn testing_png() -> Png {
@@ -131,7 +344,7 @@ mod tests {
         Png::from_chunks(chunks)
     }
 
-    fn chunk_from_strings(chunk_type: &str, data: &str) -> Result<Chunk, String> {
+    fn chunk_from_strings(chunk_type: &str, data: &str) -> Result<Chunk, PngError> {
         let chunk_type = ChunkType::from_str(chunk_type)?;
         let data: Vec<u8> = data.bytes().collect();
         Ok(Chunk::new(chunk_type, data))
@@ -178,7 +391,7 @@ mod tests {
 
         let png = Png::try_from(bytes.as_ref());
 
-        assert!(png.is_err());
+        assert!(matches!(png, Err(PngError::InvalidSignature)));
     }
 
     #[test]
@@ -203,6 +416,51 @@ mod tests {
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_truncated_png() {
+        let mut chunk_boundaries = 0;
+        for len in Png::STANDARD_HEADER.len() + 1..PNG_FILE.len() {
+            match Png::try_from(&PNG_FILE[..len]) {
+                Ok(_) => chunk_boundaries += 1,
+                Err(PngError::Truncated { offset }) => assert_eq!(offset, len),
+                Err(e) => panic!("unexpected error: {}", e),
+            }
+        }
+        assert_eq!(chunk_boundaries, 6);
+    }
+
+    #[test]
+    fn test_chunk_length_overflow() {
+        #[rustfmt::skip]
+        let bytes = [
+            137, 80, 78, 71, 13, 10, 26, 10, // header
+            255, 255, 255, 255,              // length
+            82, 117, 83, 116,                // Chunk Type
+        ];
+
+        let png = Png::try_from(bytes.as_ref());
+        assert!(matches!(
+            png,
+            Err(PngError::ChunkTooLarge { offset: 8, .. })
+        ));
+
+        let png = Png::try_from_with_max_length(bytes.as_ref(), u32::MAX);
+        assert!(matches!(png, Err(PngError::Truncated { offset: 16 })));
+    }
+
+    #[test]
+    fn test_max_chunk_length() {
+        let png = Png::try_from_with_max_length(&PNG_FILE[..], 1024);
+        assert!(matches!(
+            png,
+            Err(PngError::ChunkTooLarge {
+                length: 4681,
+                max: 1024,
+                offset: 83
+            })
+        ));
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -227,6 +485,172 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
     }
 
+    fn png_with_iend() -> Png {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.remove_chunk("RuSt").unwrap();
+        png
+    }
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_insert_before_iend() {
+        let mut png = png_with_iend();
+        png.insert_before_iend(chunk_from_strings("TeSt", "Message").unwrap());
+        assert_eq!(
+            chunk_types(&png),
+            ["IHDR", "sRGB", "gAMA", "pHYs", "IDAT", "TeSt", "IEND"]
+        );
+
+        let mut png = testing_png();
+        png.insert_before_iend(chunk_from_strings("TeSt", "Message").unwrap());
+        assert_eq!(chunk_types(&png), ["FrSt", "miDl", "LASt", "TeSt"]);
+    }
+
+    #[test]
+    fn test_insert_chunk_positions() {
+        let cases = [
+            (
+                ChunkPosition::AfterIhdr,
+                ["IHDR", "TeSt", "sRGB", "gAMA", "pHYs", "IDAT", "IEND"],
+            ),
+            (
+                ChunkPosition::BeforeFirstIdat,
+                ["IHDR", "sRGB", "gAMA", "pHYs", "TeSt", "IDAT", "IEND"],
+            ),
+            (
+                ChunkPosition::Index(2),
+                ["IHDR", "sRGB", "TeSt", "gAMA", "pHYs", "IDAT", "IEND"],
+            ),
+            (
+                ChunkPosition::End,
+                ["IHDR", "sRGB", "gAMA", "pHYs", "IDAT", "IEND", "TeSt"],
+            ),
+        ];
+
+        for (position, expected) in cases {
+            let mut png = png_with_iend();
+            png.insert_chunk(chunk_from_strings("TeSt", "Message").unwrap(), position)
+                .unwrap();
+            assert_eq!(chunk_types(&png), expected);
+        }
+    }
+
+    #[test]
+    fn test_insert_chunks_keeps_order() {
+        let mut png = png_with_iend();
+        let chunks = vec![
+            chunk_from_strings("FrSt", "1").unwrap(),
+            chunk_from_strings("SeCd", "2").unwrap(),
+        ];
+        png.insert_chunks(chunks, ChunkPosition::AfterIhdr).unwrap();
+        assert_eq!(
+            chunk_types(&png),
+            ["IHDR", "FrSt", "SeCd", "sRGB", "gAMA", "pHYs", "IDAT", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_chunks_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("FrSt", "again").unwrap());
+
+        let chunks = png.chunks_by_type("FrSt");
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].data(), b"again");
+        assert!(png.chunks_by_type("NoNe").is_empty());
+        assert!(png.chunks_by_type("bad!").is_empty());
+    }
+
+    #[test]
+    fn test_replace_image_stream() {
+        let mut png = png_with_iend();
+        let stream = png.image_stream();
+
+        png.replace_image_stream(&stream, 100).unwrap();
+        let idat = png.chunks_by_type("IDAT");
+        assert_eq!(idat.len(), stream.len().div_ceil(100));
+        assert!(idat.iter().all(|chunk| chunk.length() <= 100));
+        assert_eq!(png.image_stream(), stream);
+        assert_eq!(chunk_types(&png)[4], "IDAT");
+        assert_eq!(chunk_types(&png).last().unwrap(), "IEND");
+
+        assert!(testing_png().replace_image_stream(&stream, 100).is_err());
+        assert!(matches!(
+            png.replace_image_stream(&stream, 0),
+            Err(PngError::InvalidOutput(_))
+        ));
+        assert_eq!(png.image_stream(), stream);
+    }
+
+    #[test]
+    fn test_remove_all() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("FrSt", "again").unwrap());
+
+        let removed = png.remove_all("FrSt").unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[1].data(), b"again");
+        assert_eq!(chunk_types(&png), ["miDl", "LASt"]);
+        assert!(png.remove_all("FrSt").unwrap().is_empty());
+        assert!(png.remove_all("bad!").is_err());
+    }
+
+    #[test]
+    fn test_remove_at() {
+        let mut png = testing_png();
+
+        let removed = png.remove_at(1).unwrap();
+        assert_eq!(removed.chunk_type().to_string(), "miDl");
+        assert_eq!(chunk_types(&png), ["FrSt", "LASt"]);
+        assert!(matches!(
+            png.remove_at(2),
+            Err(PngError::IndexOutOfRange { index: 2, len: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut png = testing_png();
+
+        let removed = png.retain(|chunk| chunk.length() < 19);
+        assert_eq!(removed.len(), 2);
+        assert_eq!(chunk_types(&png), ["miDl"]);
+        assert!(png.retain(|_| true).is_empty());
+    }
+
+    #[test]
+    fn test_insert_chunk_invalid_position() {
+        let mut png = testing_png();
+        let chunk = chunk_from_strings("TeSt", "Message").unwrap();
+        assert!(matches!(
+            png.insert_chunk(chunk.clone(), ChunkPosition::AfterIhdr),
+            Err(PngError::ChunkNotFound(_))
+        ));
+        assert!(matches!(
+            png.insert_chunk(chunk, ChunkPosition::Index(4)),
+            Err(PngError::IndexOutOfRange { index: 4, len: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_chunk_position_from_str() {
+        assert_eq!(
+            ChunkPosition::from_str("before-iend").unwrap(),
+            ChunkPosition::BeforeIend
+        );
+        assert_eq!(
+            ChunkPosition::from_str("3").unwrap(),
+            ChunkPosition::Index(3)
+        );
+        assert!(ChunkPosition::from_str("middle").is_err());
+    }
+
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
@@ -236,12 +660,53 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_remove_missing_chunk() {
+        let mut png = testing_png();
+        let removed = png.remove_chunk("TeSt");
+        assert!(matches!(removed, Err(PngError::ChunkNotFound(t)) if t == "TeSt"));
+    }
+
+    #[test]
+    fn test_crc_mismatch_offset() {
+        let mut bytes = PNG_FILE.to_vec();
+        // last byte of IEND's CRC
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+
+        let png = Png::try_from(bytes.as_ref());
+
+        assert!(matches!(
+            png,
+            Err(PngError::CrcMismatch { offset, .. }) if offset == PNG_FILE.len() - 12
+        ));
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
         assert!(png.is_ok());
     }
 
+    #[test]
+    fn test_png_read_from() {
+        let png = Png::read_from(&PNG_FILE[..]).unwrap();
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_header_info() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let ihdr = png.header_info().unwrap();
+        assert_eq!((ihdr.width, ihdr.height), (50, 50));
+        assert_eq!(ihdr.bit_depth, 8);
+
+        assert!(matches!(
+            testing_png().header_info(),
+            Err(PngError::ChunkNotFound(_))
+        ));
+    }
+
     #[test]
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
@@ -0,0 +1,252 @@
+use std::io::{self, ErrorKind, Read};
+
+use crate::{
+    chunk::{Chunk, MAX_CHUNK_LENGTH},
+    chunk_type::ChunkType,
+    error::PngError,
+    png::Png,
+};
+
+/// Reads png chunks one at a time from any `Read` source.
+///
+/// The signature is checked once in `new`, after that every call to `next`
+/// reads exactly one chunk, so callers can stop early without consuming the rest
+/// of the stream. Iteration ends after the first error.
+pub struct ChunkReader<R: Read> {
+    reader: R,
+    max_length: u32,
+    offset: usize,
+    done: bool,
+}
+
+impl<R: Read> ChunkReader<R> {
+    pub fn new(reader: R) -> Result<ChunkReader<R>, PngError> {
+        ChunkReader::with_max_length(reader, MAX_CHUNK_LENGTH)
+    }
+
+    /// Same as `new`, but rejects chunks whose data is longer than `max_length`.
+    pub fn with_max_length(mut reader: R, max_length: u32) -> Result<ChunkReader<R>, PngError> {
+        let mut header_buf: [u8; 8] = Default::default();
+        reader
+            .read_exact(&mut header_buf)
+            .map_err(|e| match e.kind() {
+                ErrorKind::UnexpectedEof => PngError::InvalidSignature,
+                _ => io_error(e),
+            })?;
+        if &header_buf != Png::STANDARD_HEADER {
+            return Err(PngError::InvalidSignature);
+        }
+
+        Ok(ChunkReader {
+            reader,
+            max_length,
+            offset: header_buf.len(),
+            done: false,
+        })
+    }
+
+    /// Byte offset of the next chunk in the underlying stream.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_chunk(&mut self) -> Result<Option<Chunk>, PngError> {
+        let mut len_buf: [u8; 4] = Default::default();
+        if !self.read_exact_or_eof(&mut len_buf)? {
+            return Ok(None);
+        }
+
+        let length = u32::from_be_bytes(len_buf);
+        if length > self.max_length {
+            return Err(PngError::ChunkTooLarge {
+                length,
+                max: self.max_length,
+                offset: self.offset,
+            });
+        }
+
+        let mut type_buf: [u8; 4] = Default::default();
+        self.read_exact(&mut type_buf, 4)?;
+        let chunk_type = ChunkType::try_from(type_buf)?;
+
+        // `take` keeps a hostile length from allocating more than the stream really holds
+        let mut chunk_data = Vec::new();
+        let data_len = (&mut self.reader)
+            .take(length as u64)
+            .read_to_end(&mut chunk_data)
+            .map_err(io_error)?;
+        if data_len < length as usize {
+            return Err(PngError::Truncated {
+                offset: self.offset + 8 + data_len,
+            });
+        }
+
+        let mut crc_buf: [u8; 4] = Default::default();
+        self.read_exact(&mut crc_buf, 8 + data_len)?;
+        let expected = u32::from_be_bytes(crc_buf);
+
+        let chunk = Chunk::new(chunk_type, chunk_data);
+        if chunk.crc() != expected {
+            return Err(PngError::CrcMismatch {
+                chunk_type: chunk.chunk_type().clone(),
+                expected,
+                actual: chunk.crc(),
+                offset: self.offset,
+            });
+        }
+
+        self.offset += 12 + data_len;
+        Ok(Some(chunk))
+    }
+
+    /// Fills `buf`, returning `false` if the stream ended cleanly before the first byte.
+    fn read_exact_or_eof(&mut self, buf: &mut [u8]) -> Result<bool, PngError> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.reader.read(&mut buf[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => {
+                    return Err(PngError::Truncated {
+                        offset: self.offset + filled,
+                    })
+                }
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(io_error(e)),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Fills `buf` with bytes located `chunk_offset` bytes into the current chunk.
+    fn read_exact(&mut self, buf: &mut [u8], chunk_offset: usize) -> Result<(), PngError> {
+        match self.read_exact_or_eof(buf) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(PngError::Truncated {
+                offset: self.offset + chunk_offset,
+            }),
+            Err(PngError::Truncated { offset }) => Err(PngError::Truncated {
+                offset: offset + chunk_offset,
+            }),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = Result<Chunk, PngError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.read_chunk() {
+            Ok(Some(chunk)) => Some(Ok(chunk)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn io_error(source: io::Error) -> PngError {
+    PngError::Io {
+        path: String::from("<input>"),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG_FILE: &[u8] = include_bytes!("../test.png");
+
+    #[test]
+    fn test_read_all_chunks() {
+        let chunks: Vec<Chunk> = ChunkReader::new(PNG_FILE)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let types: Vec<String> = chunks.iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "sRGB", "gAMA", "pHYs", "IDAT", "IEND"]);
+        assert_eq!(Png::from_chunks(chunks).as_bytes(), PNG_FILE);
+    }
+
+    #[test]
+    fn test_stop_early() {
+        let mut input = PNG_FILE;
+        let mut reader = ChunkReader::new(&mut input).unwrap();
+
+        let chunk = reader.next().unwrap().unwrap();
+
+        assert_eq!(chunk.chunk_type().to_string(), "IHDR");
+        assert_eq!(reader.offset(), 33);
+        assert_eq!(input.len(), PNG_FILE.len() - 33);
+    }
+
+    #[test]
+    fn test_invalid_signature() {
+        let reader = ChunkReader::new(&PNG_FILE[1..]);
+        assert!(matches!(reader, Err(PngError::InvalidSignature)));
+
+        let reader = ChunkReader::new(&PNG_FILE[..4]);
+        assert!(matches!(reader, Err(PngError::InvalidSignature)));
+    }
+
+    #[test]
+    fn test_truncated_stream() {
+        let mut reader = ChunkReader::new(&PNG_FILE[..40]).unwrap();
+
+        assert!(reader.next().unwrap().is_ok());
+        assert!(matches!(
+            reader.next(),
+            Some(Err(PngError::Truncated { offset: 40 }))
+        ));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_hostile_length_does_not_allocate() {
+        #[rustfmt::skip]
+        let bytes = [
+            137, 80, 78, 71, 13, 10, 26, 10, // header
+            127, 255, 255, 255,              // length
+            82, 117, 83, 116,                // Chunk Type
+            1, 2, 3,                         // Data
+        ];
+        let mut reader = ChunkReader::new(bytes.as_ref()).unwrap();
+
+        assert!(matches!(
+            reader.next(),
+            Some(Err(PngError::Truncated { offset: 19 }))
+        ));
+    }
+
+    #[test]
+    fn test_max_length() {
+        let err = ChunkReader::with_max_length(PNG_FILE, 16)
+            .unwrap()
+            .find_map(|chunk| chunk.err());
+
+        assert!(matches!(
+            err,
+            Some(PngError::ChunkTooLarge {
+                max: 16,
+                offset: 83,
+                ..
+            })
+        ));
+    }
+}
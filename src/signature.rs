@@ -0,0 +1,480 @@
+use std::{fmt::Display, str::FromStr};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ed25519_dalek::{Signer, SIGNATURE_LENGTH};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    chunk::{read_array, Chunk},
+    chunk_type::ChunkType,
+    crypto::{decode_key, fill_random, key_file_line},
+    error::PngError,
+};
+
+/// Ancillary, private and unsafe to copy, since it's bound to the image data.
+pub const SIGNATURE_CHUNK_TYPE: &str = "siGN";
+
+/// Version of the signature chunk layout, bumped on incompatible changes.
+pub const SIGNATURE_VERSION: u8 = 1;
+
+/// Prepended to the signed message, so the signature can't be replayed elsewhere.
+const DOMAIN: &[u8] = b"pngme signature\0";
+
+const PUBLIC_KEY_LENGTH: usize = 32;
+
+/// Ed25519 key that signs pngs, written as `PNGME-SIGNING-KEY:<base64>`.
+///
+/// It's deliberately not `Display` nor `Debug`, so it doesn't end up in logs.
+#[derive(Clone)]
+pub struct SigningKey(ed25519_dalek::SigningKey);
+
+impl SigningKey {
+    pub const PREFIX: &'static str = "PNGME-SIGNING-KEY:";
+
+    pub fn generate() -> Result<SigningKey, PngError> {
+        let mut secret = [0; 32];
+        fill_random(&mut secret)?;
+        Ok(SigningKey(ed25519_dalek::SigningKey::from_bytes(&secret)))
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        VerifyingKey(self.0.verifying_key())
+    }
+
+    /// Contents of a key file, the public key is included as a comment.
+    pub fn to_key_file(&self) -> String {
+        format!(
+            "# public key: {}\n{}{}\n",
+            self.verifying_key(),
+            SigningKey::PREFIX,
+            BASE64.encode(self.0.to_bytes())
+        )
+    }
+}
+
+impl FromStr for SigningKey {
+    type Err = PngError;
+
+    /// Parses a key file, skipping empty lines and `#` comments.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = decode_key(key_file_line(s)?, SigningKey::PREFIX)?;
+        Ok(SigningKey(ed25519_dalek::SigningKey::from_bytes(&bytes)))
+    }
+}
+
+/// Public half of a `SigningKey`, written as `pngme-sign-pub:<base64>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyingKey(ed25519_dalek::VerifyingKey);
+
+impl VerifyingKey {
+    pub const PREFIX: &'static str = "pngme-sign-pub:";
+
+    fn from_bytes(bytes: &[u8; PUBLIC_KEY_LENGTH]) -> Result<VerifyingKey, PngError> {
+        ed25519_dalek::VerifyingKey::from_bytes(bytes)
+            .map(VerifyingKey)
+            .map_err(|_| PngError::InvalidKey(String::from("not a valid Ed25519 public key")))
+    }
+}
+
+impl FromStr for VerifyingKey {
+    type Err = PngError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        VerifyingKey::from_bytes(&decode_key(s.trim(), VerifyingKey::PREFIX)?)
+    }
+}
+
+impl Display for VerifyingKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            VerifyingKey::PREFIX,
+            BASE64.encode(self.0.as_bytes())
+        )
+    }
+}
+
+/// Chunks covered by a signature, besides the ones listed in `SignatureScope::include`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    /// Every chunk, any edit breaks the signature.
+    All = 0,
+    /// Only critical chunks, i.e. the image itself.
+    Critical = 1,
+    /// Critical chunks and ancillary chunks that aren't safe to copy, so editors
+    /// that only touch safe-to-copy chunks keep the signature valid.
+    UnsafeToCopy = 2,
+}
+
+impl TryFrom<u8> for ScopeKind {
+    type Error = PngError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ScopeKind::All),
+            1 => Ok(ScopeKind::Critical),
+            2 => Ok(ScopeKind::UnsafeToCopy),
+            other => Err(PngError::InvalidSignatureChunk(format!(
+                "unknown scope {}",
+                other
+            ))),
+        }
+    }
+}
+
+impl FromStr for ScopeKind {
+    type Err = PngError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(ScopeKind::All),
+            "critical" => Ok(ScopeKind::Critical),
+            "unsafe-to-copy" => Ok(ScopeKind::UnsafeToCopy),
+            other => Err(PngError::InvalidFormat(other.to_string())),
+        }
+    }
+}
+
+impl Display for ScopeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ScopeKind::All => "all",
+            ScopeKind::Critical => "critical",
+            ScopeKind::UnsafeToCopy => "unsafe-to-copy",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Chunks a signature covers. Signature chunks themselves are never covered,
+/// so a png can carry signatures of several keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureScope {
+    pub kind: ScopeKind,
+    /// Chunk types covered on top of `kind`, e.g. the payload chunk. At most 255.
+    pub include: Vec<ChunkType>,
+}
+
+impl SignatureScope {
+    pub fn covers(&self, chunk_type: &ChunkType) -> bool {
+        if chunk_type.bytes() == SIGNATURE_CHUNK_TYPE.as_bytes() {
+            return false;
+        }
+
+        self.include.contains(chunk_type)
+            || match self.kind {
+                ScopeKind::All => true,
+                ScopeKind::Critical => chunk_type.is_critical(),
+                ScopeKind::UnsafeToCopy => {
+                    chunk_type.is_critical() || !chunk_type.is_safe_to_copy()
+                }
+            }
+    }
+}
+
+/// Ed25519 signature stored in a `siGN` chunk, along with its scope and the
+/// public key of the signer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkSignature {
+    scope: SignatureScope,
+    key: VerifyingKey,
+    signature: ed25519_dalek::Signature,
+}
+
+impl ChunkSignature {
+    /// Signs the chunks of a png that `scope` covers, in their order.
+    pub fn sign(chunks: &[&Chunk], key: &SigningKey, scope: SignatureScope) -> ChunkSignature {
+        let verifying_key = key.verifying_key();
+        let message = signed_message(chunks, &scope, &verifying_key);
+
+        ChunkSignature {
+            scope,
+            key: verifying_key,
+            signature: key.0.sign(&message),
+        }
+    }
+
+    pub fn scope(&self) -> &SignatureScope {
+        &self.scope
+    }
+
+    pub fn key(&self) -> &VerifyingKey {
+        &self.key
+    }
+
+    /// Recomputes the digest of `chunks`, failing with `PngError::SignatureMismatch`
+    /// if any covered chunk was changed, added or removed since signing.
+    pub fn verify(&self, chunks: &[&Chunk]) -> Result<(), PngError> {
+        let message = signed_message(chunks, &self.scope, &self.key);
+        self.key
+            .0
+            .verify_strict(&message, &self.signature)
+            .map_err(|_| PngError::SignatureMismatch)
+    }
+
+    /// Layout: version, scope kind, count of included types, the types,
+    /// public key and the signature.
+    pub fn to_chunk(&self) -> Chunk {
+        let data: Vec<u8> = header(&self.scope, &self.key)
+            .into_iter()
+            .chain(self.signature.to_bytes())
+            .collect();
+
+        Chunk::new(
+            ChunkType::from_str(SIGNATURE_CHUNK_TYPE).expect("siGN is a valid chunk type"),
+            data,
+        )
+    }
+
+    /// Whether `chunk` is a `siGN` chunk, no matter if its data is valid.
+    pub fn is_signature_chunk(chunk: &Chunk) -> bool {
+        chunk.chunk_type().bytes() == SIGNATURE_CHUNK_TYPE.as_bytes()
+    }
+}
+
+impl TryFrom<&Chunk> for ChunkSignature {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        let truncated = || PngError::InvalidSignatureChunk(String::from("data is truncated"));
+
+        if !ChunkSignature::is_signature_chunk(chunk) {
+            return Err(PngError::InvalidSignatureChunk(format!(
+                "expected {} chunk, got {}",
+                SIGNATURE_CHUNK_TYPE,
+                chunk.chunk_type()
+            )));
+        }
+
+        let data = chunk.data();
+        let [version, kind, count] = read_array(data, 0).map_err(|_| truncated())?;
+        if version != SIGNATURE_VERSION {
+            return Err(PngError::InvalidSignatureChunk(format!(
+                "unsupported version {}",
+                version
+            )));
+        }
+
+        let mut offset = 3;
+        let mut include = Vec::new();
+        for _ in 0..count {
+            let bytes: [u8; 4] = read_array(data, offset).map_err(|_| truncated())?;
+            include.push(ChunkType::try_from(bytes)?);
+            offset += 4;
+        }
+
+        let key = VerifyingKey::from_bytes(&read_array(data, offset).map_err(|_| truncated())?)?;
+        offset += PUBLIC_KEY_LENGTH;
+        let signature: [u8; SIGNATURE_LENGTH] =
+            read_array(data, offset).map_err(|_| truncated())?;
+        if data.len() != offset + SIGNATURE_LENGTH {
+            return Err(PngError::InvalidSignatureChunk(String::from(
+                "unexpected data after signature",
+            )));
+        }
+
+        Ok(ChunkSignature {
+            scope: SignatureScope {
+                kind: ScopeKind::try_from(kind)?,
+                include,
+            },
+            key,
+            signature: ed25519_dalek::Signature::from_bytes(&signature),
+        })
+    }
+}
+
+fn header(scope: &SignatureScope, key: &VerifyingKey) -> Vec<u8> {
+    [
+        SIGNATURE_VERSION,
+        scope.kind as u8,
+        scope.include.len() as u8,
+    ]
+    .into_iter()
+    .chain(
+        scope
+            .include
+            .iter()
+            .flat_map(|chunk_type| chunk_type.bytes()),
+    )
+    .chain(key.0.to_bytes())
+    .collect()
+}
+
+/// Domain, header and SHA-256 of length, type and data of every covered chunk.
+fn signed_message(chunks: &[&Chunk], scope: &SignatureScope, key: &VerifyingKey) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    for chunk in chunks
+        .iter()
+        .filter(|chunk| scope.covers(chunk.chunk_type()))
+    {
+        hasher.update(chunk.length().to_be_bytes());
+        hasher.update(chunk.chunk_type().bytes());
+        hasher.update(chunk.data());
+    }
+
+    DOMAIN
+        .iter()
+        .copied()
+        .chain(header(scope, key))
+        .chain(hasher.finalize())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::Png;
+
+    const PNG_FILE: &[u8] = include_bytes!("../test.png");
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn testing_chunks() -> Vec<Chunk> {
+        let mut chunks: Vec<Chunk> = Png::try_from(PNG_FILE)
+            .unwrap()
+            .chunks()
+            .into_iter()
+            .cloned()
+            .collect();
+        chunks.insert(5, chunk("ruSt", b"hidden message"));
+        chunks
+    }
+
+    fn scope(kind: ScopeKind) -> SignatureScope {
+        SignatureScope {
+            kind,
+            include: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = SigningKey::generate().unwrap();
+        let chunks = testing_chunks();
+        let refs: Vec<&Chunk> = chunks.iter().collect();
+
+        let signature = ChunkSignature::sign(&refs, &key, scope(ScopeKind::All));
+        assert!(signature.verify(&refs).is_ok());
+        assert_eq!(signature.key(), &key.verifying_key());
+
+        let parsed = ChunkSignature::try_from(&signature.to_chunk()).unwrap();
+        assert_eq!(parsed, signature);
+
+        // Signature chunks are skipped, adding one doesn't break the others.
+        let mut with_signature = refs.clone();
+        let signature_chunk = signature.to_chunk();
+        with_signature.insert(6, &signature_chunk);
+        assert!(parsed.verify(&with_signature).is_ok());
+    }
+
+    #[test]
+    fn test_modified_chunk_breaks_signature() {
+        let key = SigningKey::generate().unwrap();
+        let mut chunks = testing_chunks();
+        let signature = ChunkSignature::sign(
+            &chunks.iter().collect::<Vec<_>>(),
+            &key,
+            scope(ScopeKind::All),
+        );
+
+        chunks[5] = chunk("ruSt", b"tampered message");
+        assert!(matches!(
+            signature.verify(&chunks.iter().collect::<Vec<_>>()),
+            Err(PngError::SignatureMismatch)
+        ));
+
+        chunks.remove(5);
+        assert!(signature
+            .verify(&chunks.iter().collect::<Vec<_>>())
+            .is_err());
+    }
+
+    #[test]
+    fn test_scope_allows_safe_to_copy_edits() {
+        let key = SigningKey::generate().unwrap();
+        let mut chunks = testing_chunks();
+        let include = SignatureScope {
+            kind: ScopeKind::UnsafeToCopy,
+            include: vec![ChunkType::from_str("ruSt").unwrap()],
+        };
+        let signature = ChunkSignature::sign(&chunks.iter().collect::<Vec<_>>(), &key, include);
+
+        chunks.insert(5, chunk("tEXt", b"Comment\0edited later"));
+        chunks[3] = chunk("pHYs", &[0; 9]);
+        assert!(signature.verify(&chunks.iter().collect::<Vec<_>>()).is_ok());
+
+        chunks.insert(5, chunk("ruST", b"unsafe to copy"));
+        assert!(signature
+            .verify(&chunks.iter().collect::<Vec<_>>())
+            .is_err());
+
+        let chunks = testing_chunks();
+        let signature = ChunkSignature::sign(
+            &chunks.iter().collect::<Vec<_>>(),
+            &key,
+            scope(ScopeKind::Critical),
+        );
+        let mut edited = chunks.clone();
+        edited[5] = chunk("ruSt", b"payload isn't covered");
+        assert!(signature.verify(&edited.iter().collect::<Vec<_>>()).is_ok());
+    }
+
+    #[test]
+    fn test_wrong_key_in_chunk() {
+        let key = SigningKey::generate().unwrap();
+        let other = SigningKey::generate().unwrap();
+        let chunks = testing_chunks();
+        let refs: Vec<&Chunk> = chunks.iter().collect();
+
+        let mut data = ChunkSignature::sign(&refs, &key, scope(ScopeKind::All))
+            .to_chunk()
+            .data()
+            .to_vec();
+        data[3..35].copy_from_slice(other.verifying_key().0.as_bytes());
+        let forged = ChunkSignature::try_from(&chunk(SIGNATURE_CHUNK_TYPE, &data)).unwrap();
+        assert!(forged.verify(&refs).is_err());
+    }
+
+    #[test]
+    fn test_invalid_signature_chunk() {
+        let key = SigningKey::generate().unwrap();
+        let data = ChunkSignature::sign(&[], &key, scope(ScopeKind::All))
+            .to_chunk()
+            .data()
+            .to_vec();
+
+        let mut wrong_version = data.clone();
+        wrong_version[0] = 2;
+        let mut wrong_kind = data.clone();
+        wrong_kind[1] = 9;
+        let mut trailing = data.clone();
+        trailing.push(0);
+
+        for data in [&data[..50], &wrong_version, &wrong_kind, &trailing] {
+            assert!(matches!(
+                ChunkSignature::try_from(&chunk(SIGNATURE_CHUNK_TYPE, data)),
+                Err(PngError::InvalidSignatureChunk(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_key_encoding() {
+        let key = SigningKey::generate().unwrap();
+        let key_file = key.to_key_file();
+        let parsed = SigningKey::from_str(&key_file).unwrap();
+        assert_eq!(parsed.verifying_key(), key.verifying_key());
+
+        let public = key.verifying_key().to_string();
+        assert!(key_file.contains(&public));
+        assert_eq!(
+            VerifyingKey::from_str(&public).unwrap(),
+            key.verifying_key()
+        );
+        assert!(VerifyingKey::from_str("pngme-pub:AAAA").is_err());
+    }
+}
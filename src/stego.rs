@@ -0,0 +1,303 @@
+use crate::{
+    error::PngError,
+    ihdr::{ColorType, Ihdr},
+    image::{decode_idat, encode_idat, filter_distance, DEFAULT_IDAT_LENGTH},
+    png::Png,
+};
+
+/// Marks the start of a payload hidden in pixel data.
+const MAGIC: &[u8; 4] = b"PMEl";
+
+/// Magic and u32 payload length, hidden in front of the payload.
+const HEADER_LENGTH: usize = 4 + 4;
+
+/// Number of payload bytes that fit into the least significant bits of `png`.
+pub fn capacity(png: &Png) -> Result<usize, PngError> {
+    let ihdr = png.header_info()?;
+    check_supported(&ihdr)?;
+
+    let carriers = sample_count(&ihdr)? / ihdr.color_type.channels() * carrier_channels(&ihdr);
+    Ok((carriers / 8).saturating_sub(HEADER_LENGTH))
+}
+
+/// Hides `payload` in the least significant bit of each color sample, leaving
+/// alpha alone. Every used sample changes by at most one and the `IDAT` chunks
+/// are rewritten with the filters they had.
+pub fn embed(png: &mut Png, payload: &[u8]) -> Result<(), PngError> {
+    let ihdr = png.header_info()?;
+    let capacity = capacity(png)?;
+    if payload.len() > capacity || payload.len() > u32::MAX as usize {
+        return Err(PngError::CapacityExceeded {
+            needed: payload.len(),
+            capacity,
+        });
+    }
+    let mut scanlines = decode_idat(&ihdr, &png.image_stream())?;
+
+    let hidden: Vec<u8> = MAGIC
+        .iter()
+        .copied()
+        .chain((payload.len() as u32).to_be_bytes())
+        .chain(payload.iter().copied())
+        .collect();
+    let bits = hidden
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |shift| (byte >> shift) & 1));
+    for (index, bit) in carrier_indexes(&ihdr)?.zip(bits) {
+        scanlines.data[index] = (scanlines.data[index] & !1) | bit;
+    }
+
+    let stream = encode_idat(&scanlines, filter_distance(&ihdr));
+    png.replace_image_stream(&stream, DEFAULT_IDAT_LENGTH)
+}
+
+/// Recovers a payload hidden by `embed`. Only the bits of the header and the
+/// payload it declares are read.
+pub fn extract(png: &Png) -> Result<Vec<u8>, PngError> {
+    let ihdr = png.header_info()?;
+    let capacity = capacity(png)?;
+    let scanlines = decode_idat(&ihdr, &png.image_stream())?;
+    let mut bits = carrier_indexes(&ihdr)?.map(|index| scanlines.data[index] & 1);
+
+    let header = read_bytes(&mut bits, HEADER_LENGTH);
+    if header.len() < HEADER_LENGTH || !header.starts_with(MAGIC) {
+        return Err(PngError::InvalidPayload(String::from(
+            "no payload hidden in pixel data",
+        )));
+    }
+    let length = u32::from_be_bytes(header[4..].try_into().expect("4 bytes")) as usize;
+    if length > capacity {
+        return Err(PngError::InvalidPayload(format!(
+            "hidden payload declares {} bytes, image holds at most {}",
+            length, capacity
+        )));
+    }
+
+    Ok(read_bytes(&mut bits, length))
+}
+
+/// Packs up to `count` bytes from `bits`, most significant bit first.
+fn read_bytes(bits: &mut impl Iterator<Item = u8>, count: usize) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut byte = 0;
+        for _ in 0..8 {
+            match bits.next() {
+                Some(bit) => byte = (byte << 1) | bit,
+                None => return bytes,
+            }
+        }
+        bytes.push(byte);
+    }
+    bytes
+}
+
+fn check_supported(ihdr: &Ihdr) -> Result<(), PngError> {
+    if ihdr.color_type == ColorType::Indexed {
+        return Err(PngError::UnsupportedImage(String::from(
+            "indexed colors can't hide data in their samples",
+        )));
+    }
+    if ihdr.bit_depth < 8 {
+        return Err(PngError::UnsupportedImage(format!(
+            "bit depth {} is too low to hide data",
+            ihdr.bit_depth
+        )));
+    }
+    if ihdr.is_interlaced() {
+        return Err(PngError::UnsupportedImage(String::from(
+            "interlaced images are not supported",
+        )));
+    }
+    Ok(())
+}
+
+/// Channels of a pixel that carry hidden bits, alpha excluded.
+fn carrier_channels(ihdr: &Ihdr) -> usize {
+    let channels = ihdr.color_type.channels();
+    match ihdr.color_type.has_alpha() {
+        true => channels - 1,
+        false => channels,
+    }
+}
+
+/// Number of samples in the image, failing when their bytes can't be indexed.
+fn sample_count(ihdr: &Ihdr) -> Result<usize, PngError> {
+    let samples = (ihdr.width as usize)
+        .checked_mul(ihdr.height as usize)
+        .and_then(|pixels| pixels.checked_mul(ihdr.color_type.channels()));
+    match samples {
+        Some(samples) if samples.checked_mul(ihdr.bit_depth as usize / 8).is_some() => Ok(samples),
+        _ => Err(PngError::UnsupportedImage(format!(
+            "{}x{} pixels are too many to address",
+            ihdr.width, ihdr.height
+        ))),
+    }
+}
+
+/// Indexes into unfiltered scanlines of the least significant byte of every
+/// color sample, in pixel order.
+fn carrier_indexes(ihdr: &Ihdr) -> Result<impl Iterator<Item = usize>, PngError> {
+    let sample_length = ihdr.bit_depth as usize / 8;
+    let channels = ihdr.color_type.channels();
+    let carriers = carrier_channels(ihdr);
+    let samples = sample_count(ihdr)?;
+
+    Ok((0..samples)
+        .filter(move |sample| sample % channels < carriers)
+        .map(move |sample| sample * sample_length + sample_length - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chunk::Chunk,
+        chunk_type::ChunkType,
+        image::{FilterType, Scanlines},
+    };
+    use std::str::FromStr;
+
+    const PNG_FILE: &[u8] = include_bytes!("../test.png");
+
+    fn pixels(png: &Png) -> Scanlines {
+        decode_idat(&png.header_info().unwrap(), &png.image_stream()).unwrap()
+    }
+
+    /// A png of the given format whose samples count up from 0.
+    fn generated_png(bit_depth: u8, color_type: ColorType) -> Png {
+        let ihdr = Ihdr {
+            width: 20,
+            height: 10,
+            bit_depth,
+            color_type,
+            compression: 0,
+            filter: 0,
+            interlace: 0,
+        };
+        let row_length = ihdr.row_length(ihdr.width);
+        let scanlines = Scanlines {
+            row_length,
+            filters: vec![FilterType::Paeth; 10],
+            data: (0..row_length * 10).map(|i| (i % 251) as u8).collect(),
+        };
+        Png::from_chunks(vec![
+            ihdr.to_chunk(),
+            Chunk::new(
+                ChunkType::from_str("IDAT").unwrap(),
+                encode_idat(&scanlines, filter_distance(&ihdr)),
+            ),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()),
+        ])
+    }
+
+    #[test]
+    fn test_embed_and_extract() {
+        let mut png = Png::try_from(PNG_FILE).unwrap();
+        let before = pixels(&png);
+        assert_eq!(capacity(&png).unwrap(), 135 * 114 * 3 / 8 - HEADER_LENGTH);
+
+        embed(&mut png, b"hidden message").unwrap();
+        assert_eq!(extract(&png).unwrap(), b"hidden message");
+
+        let after = pixels(&png);
+        assert_eq!(after.filters, before.filters);
+        assert!(before
+            .data
+            .iter()
+            .zip(&after.data)
+            .all(|(old, new)| old.abs_diff(*new) <= 1));
+        assert_eq!(
+            png.chunks().last().unwrap().chunk_type().to_string(),
+            "IEND"
+        );
+    }
+
+    #[test]
+    fn test_embed_in_other_formats() {
+        for (bit_depth, color_type) in [
+            (8, ColorType::Grayscale),
+            (16, ColorType::Grayscale),
+            (8, ColorType::GrayscaleAlpha),
+            (16, ColorType::Rgb),
+            (8, ColorType::Rgba),
+            (16, ColorType::Rgba),
+        ] {
+            let mut png = generated_png(bit_depth, color_type);
+            let before = pixels(&png);
+            let payload = vec![0xa5; capacity(&png).unwrap()];
+
+            embed(&mut png, &payload).unwrap();
+            assert_eq!(extract(&png).unwrap(), payload);
+
+            let after = pixels(&png);
+            let sample_length = bit_depth as usize / 8;
+            for (index, (old, new)) in before.data.iter().zip(&after.data).enumerate() {
+                let sample = index / sample_length;
+                let is_alpha = color_type.has_alpha()
+                    && sample % color_type.channels() == color_type.channels() - 1;
+                let is_high_byte = index % sample_length != sample_length - 1;
+                if is_alpha || is_high_byte {
+                    assert_eq!(old, new);
+                } else {
+                    assert!(old.abs_diff(*new) <= 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_capacity_exceeded() {
+        let mut png = generated_png(8, ColorType::Grayscale);
+        let capacity = capacity(&png).unwrap();
+        assert_eq!(capacity, 20 * 10 / 8 - HEADER_LENGTH);
+        assert!(matches!(
+            embed(&mut png, &vec![0; capacity + 1]),
+            Err(PngError::CapacityExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_images() {
+        let ihdr = Png::try_from(PNG_FILE).unwrap().header_info().unwrap();
+        for ihdr in [
+            Ihdr {
+                color_type: ColorType::Indexed,
+                ..ihdr.clone()
+            },
+            Ihdr {
+                color_type: ColorType::Grayscale,
+                bit_depth: 4,
+                ..ihdr.clone()
+            },
+            Ihdr {
+                interlace: 1,
+                ..ihdr
+            },
+        ] {
+            let png = Png::from_chunks(vec![ihdr.to_chunk()]);
+            assert!(matches!(capacity(&png), Err(PngError::UnsupportedImage(_))));
+        }
+    }
+
+    #[test]
+    fn test_capacity_overflow() {
+        let ihdr = Ihdr {
+            width: i32::MAX as u32,
+            height: i32::MAX as u32,
+            bit_depth: 16,
+            color_type: ColorType::Rgba,
+            compression: 0,
+            filter: 0,
+            interlace: 0,
+        };
+        let png = Png::from_chunks(vec![ihdr.to_chunk()]);
+        assert!(matches!(capacity(&png), Err(PngError::UnsupportedImage(_))));
+    }
+
+    #[test]
+    fn test_extract_without_payload() {
+        let png = Png::try_from(PNG_FILE).unwrap();
+        assert!(matches!(extract(&png), Err(PngError::InvalidPayload(_))));
+    }
+}
@@ -0,0 +1,191 @@
+use std::fmt::Display;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png, text::keyword_of};
+
+/// Text keywords that tend to name people, devices or places. `XML:com.adobe.xmp`
+/// holds XMP metadata, which carries the same information as Exif.
+const IDENTIFYING_KEYWORDS: [&str; 7] = [
+    "Author",
+    "Copyright",
+    "Creation Time",
+    "Software",
+    "Source",
+    "Comment",
+    "XML:com.adobe.xmp",
+];
+
+/// Which ancillary chunks `strip` removes. Critical chunks are always kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StripPreset {
+    /// Identifying text, `eXIf`, `tIME`, `iCCP` and private ancillary chunks.
+    #[default]
+    Privacy,
+    /// Every ancillary chunk.
+    AllAncillary,
+}
+
+/// Chunk removed by `strip`, with the reason it was dropped.
+#[derive(Debug, Clone)]
+pub struct StrippedChunk {
+    pub chunk: Chunk,
+    pub reason: &'static str,
+}
+
+impl Display for StrippedChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.chunk.chunk_type())?;
+        if let Some(keyword) = keyword_of(&self.chunk) {
+            write!(f, " {:?}", keyword)?;
+        }
+        write!(f, " ({} bytes): {}", self.chunk.length(), self.reason)
+    }
+}
+
+/// Removes the chunks selected by `preset`, except those of a type in `keep`,
+/// and returns them in file order.
+pub fn strip(png: &mut Png, preset: StripPreset, keep: &[ChunkType]) -> Vec<StrippedChunk> {
+    let dropped =
+        |chunk: &Chunk| !keep.contains(chunk.chunk_type()) && strip_reason(chunk, preset).is_some();
+
+    png.retain(|chunk| !dropped(chunk))
+        .into_iter()
+        .map(|chunk| {
+            let reason = strip_reason(&chunk, preset).expect("only stripped chunks are removed");
+            StrippedChunk { chunk, reason }
+        })
+        .collect()
+}
+
+/// Why `preset` drops `chunk`, `None` when it is kept.
+fn strip_reason(chunk: &Chunk, preset: StripPreset) -> Option<&'static str> {
+    let chunk_type = chunk.chunk_type();
+    if chunk_type.is_critical() {
+        return None;
+    }
+
+    let privacy_reason = match &chunk_type.bytes() {
+        b"eXIf" => Some("Exif metadata, may hold GPS position and camera"),
+        b"tIME" => Some("last modification time"),
+        b"iCCP" => Some("ICC profile, may name the device"),
+        _ if !chunk_type.is_public() => Some("private chunk"),
+        _ => keyword_of(chunk)
+            .filter(|keyword| IDENTIFYING_KEYWORDS.contains(&keyword.as_str()))
+            .map(|_| "identifying text"),
+    };
+
+    match preset {
+        StripPreset::Privacy => privacy_reason,
+        StripPreset::AllAncillary => privacy_reason.or(Some("ancillary chunk")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::{CompressedTextChunk, InternationalTextChunk, TextChunk};
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn metadata_png() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            chunk("iCCP", b"Camera XYZ\0\0profile"),
+            chunk("gAMA", &[0, 0, 177, 143]),
+            chunk("pHYs", &[0, 0, 11, 19, 0, 0, 11, 19, 1]),
+            chunk("eXIf", b"MM\0*GPS"),
+            TextChunk::new("Title", "Holiday").unwrap().to_chunk(),
+            TextChunk::new("Author", "Jane Doe").unwrap().to_chunk(),
+            CompressedTextChunk::new("Software", "Phone OS")
+                .unwrap()
+                .to_chunk(),
+            InternationalTextChunk::new("XML:com.adobe.xmp", "", "", "<x/>", false)
+                .unwrap()
+                .to_chunk(),
+            chunk("ruSt", b"private"),
+            chunk("PrSt", b"private critical"),
+            chunk("IDAT", &[1, 2, 3]),
+            chunk("tIME", &[7, 234, 10, 18, 12, 0, 0]),
+            chunk("IEND", &[]),
+        ])
+    }
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_strip_privacy() {
+        let mut png = metadata_png();
+        let stripped = strip(&mut png, StripPreset::Privacy, &[]);
+
+        assert_eq!(
+            chunk_types(&png),
+            ["IHDR", "gAMA", "pHYs", "tEXt", "PrSt", "IDAT", "IEND"]
+        );
+        assert_eq!(keyword_of(png.chunks()[3]).unwrap(), "Title");
+        let dropped: Vec<(String, &str)> = stripped
+            .iter()
+            .map(|s| (s.chunk.chunk_type().to_string(), s.reason))
+            .collect();
+        assert_eq!(
+            dropped,
+            [
+                ("iCCP".to_string(), "ICC profile, may name the device"),
+                (
+                    "eXIf".to_string(),
+                    "Exif metadata, may hold GPS position and camera"
+                ),
+                ("tEXt".to_string(), "identifying text"),
+                ("zTXt".to_string(), "identifying text"),
+                ("iTXt".to_string(), "identifying text"),
+                ("ruSt".to_string(), "private chunk"),
+                ("tIME".to_string(), "last modification time"),
+            ]
+        );
+        assert_eq!(
+            stripped[2].to_string(),
+            "tEXt \"Author\" (15 bytes): identifying text"
+        );
+        assert_eq!(stripped[5].to_string(), "ruSt (7 bytes): private chunk");
+    }
+
+    #[test]
+    fn test_strip_all_ancillary() {
+        let mut png = metadata_png();
+        let stripped = strip(&mut png, StripPreset::AllAncillary, &[]);
+
+        assert_eq!(chunk_types(&png), ["IHDR", "PrSt", "IDAT", "IEND"]);
+        assert_eq!(stripped.len(), 10);
+        assert_eq!(stripped[1].reason, "ancillary chunk");
+        assert_eq!(stripped[1].chunk.chunk_type().to_string(), "gAMA");
+    }
+
+    #[test]
+    fn test_strip_keep() {
+        let mut png = metadata_png();
+        let keep = [
+            ChunkType::from_str("iCCP").unwrap(),
+            ChunkType::from_str("tEXt").unwrap(),
+            ChunkType::from_str("IDAT").unwrap(),
+        ];
+        strip(&mut png, StripPreset::AllAncillary, &keep);
+
+        assert_eq!(
+            chunk_types(&png),
+            ["IHDR", "iCCP", "tEXt", "tEXt", "PrSt", "IDAT", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_strip_nothing() {
+        let mut png = Png::from_chunks(vec![chunk("IHDR", &[0; 13]), chunk("IEND", &[])]);
+        assert!(strip(&mut png, StripPreset::AllAncillary, &[]).is_empty());
+        assert_eq!(chunk_types(&png), ["IHDR", "IEND"]);
+    }
+}
@@ -0,0 +1,557 @@
+use std::str::FromStr;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    compression::{deflate, inflate},
+    error::PngError,
+};
+
+/// Upper bound of inflated `zTXt` and `iTXt` text.
+pub const MAX_TEXT_LENGTH: usize = 16 * 1024 * 1024;
+
+/// Keyword/value pair stored in a standard `tEXt` chunk.
+///
+/// Both parts are Latin-1 on disk, they're kept as `String` and checked to only
+/// contain characters that Latin-1 can represent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    keyword: String,
+    text: String,
+}
+
+impl TextChunk {
+    pub const CHUNK_TYPE: &'static str = "tEXt";
+
+    pub fn new(keyword: &str, text: &str) -> Result<TextChunk, PngError> {
+        validate_keyword(keyword)?;
+        validate_latin1_text(text)?;
+
+        Ok(TextChunk {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+        })
+    }
+
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let data: Vec<u8> = encode_latin1(&self.keyword)
+            .into_iter()
+            .chain([0])
+            .chain(encode_latin1(&self.text))
+            .collect();
+
+        new_chunk(Self::CHUNK_TYPE, data)
+    }
+
+    /// Whether `chunk` is a `tEXt` chunk, no matter if its data is valid.
+    pub fn is_text_chunk(chunk: &Chunk) -> bool {
+        has_type(chunk, Self::CHUNK_TYPE)
+    }
+}
+
+impl TryFrom<&Chunk> for TextChunk {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        expect_type(chunk, TextChunk::CHUNK_TYPE)?;
+
+        let (keyword, text) = split_keyword(chunk.data())?;
+        TextChunk::new(&decode_latin1(keyword), &decode_latin1(text))
+    }
+}
+
+/// Latin-1 keyword/value pair stored zlib-compressed in a `zTXt` chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedTextChunk {
+    keyword: String,
+    text: String,
+}
+
+impl CompressedTextChunk {
+    pub const CHUNK_TYPE: &'static str = "zTXt";
+
+    pub fn new(keyword: &str, text: &str) -> Result<CompressedTextChunk, PngError> {
+        validate_keyword(keyword)?;
+        validate_latin1_text(text)?;
+
+        Ok(CompressedTextChunk {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+        })
+    }
+
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let data: Vec<u8> = encode_latin1(&self.keyword)
+            .into_iter()
+            .chain([0, COMPRESSION_METHOD_DEFLATE])
+            .chain(deflate(&encode_latin1(&self.text)))
+            .collect();
+
+        new_chunk(Self::CHUNK_TYPE, data)
+    }
+
+    /// Whether `chunk` is a `zTXt` chunk, no matter if its data is valid.
+    pub fn is_compressed_text_chunk(chunk: &Chunk) -> bool {
+        has_type(chunk, Self::CHUNK_TYPE)
+    }
+}
+
+impl TryFrom<&Chunk> for CompressedTextChunk {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        expect_type(chunk, CompressedTextChunk::CHUNK_TYPE)?;
+
+        let (keyword, rest) = split_keyword(chunk.data())?;
+        let (method, compressed) =
+            rest.split_first()
+                .ok_or(PngError::InvalidText(String::from(
+                    "compression method is missing",
+                )))?;
+        check_compression_method(*method)?;
+
+        let text = inflate(compressed, MAX_TEXT_LENGTH)?;
+        CompressedTextChunk::new(&decode_latin1(keyword), &decode_latin1(&text))
+    }
+}
+
+/// UTF-8 text stored in an `iTXt` chunk, along with the language it's written
+/// in and the keyword translated to that language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternationalTextChunk {
+    keyword: String,
+    language_tag: String,
+    translated_keyword: String,
+    text: String,
+    compressed: bool,
+}
+
+impl InternationalTextChunk {
+    pub const CHUNK_TYPE: &'static str = "iTXt";
+
+    /// `language_tag` may be empty when the language is unknown, e.g. `en-GB`
+    /// otherwise. The text is deflated on disk when `compressed` is set.
+    pub fn new(
+        keyword: &str,
+        language_tag: &str,
+        translated_keyword: &str,
+        text: &str,
+        compressed: bool,
+    ) -> Result<InternationalTextChunk, PngError> {
+        validate_keyword(keyword)?;
+        if !language_tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            return Err(PngError::InvalidText(format!(
+                "invalid language tag {:?}",
+                language_tag
+            )));
+        }
+        if translated_keyword.contains('\0') {
+            return Err(PngError::InvalidText(String::from(
+                "translated keyword must not contain NUL characters",
+            )));
+        }
+        if text.contains('\0') {
+            return Err(PngError::InvalidText(String::from(
+                "text must not contain NUL characters",
+            )));
+        }
+
+        Ok(InternationalTextChunk {
+            keyword: keyword.to_string(),
+            language_tag: language_tag.to_string(),
+            translated_keyword: translated_keyword.to_string(),
+            text: text.to_string(),
+            compressed,
+        })
+    }
+
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    pub fn language_tag(&self) -> &str {
+        &self.language_tag
+    }
+
+    pub fn translated_keyword(&self) -> &str {
+        &self.translated_keyword
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let text = if self.compressed {
+            deflate(self.text.as_bytes())
+        } else {
+            self.text.as_bytes().to_vec()
+        };
+
+        let data: Vec<u8> = encode_latin1(&self.keyword)
+            .into_iter()
+            .chain([0, self.compressed as u8, COMPRESSION_METHOD_DEFLATE])
+            .chain(self.language_tag.bytes())
+            .chain([0])
+            .chain(self.translated_keyword.bytes())
+            .chain([0])
+            .chain(text)
+            .collect();
+
+        new_chunk(Self::CHUNK_TYPE, data)
+    }
+
+    /// Whether `chunk` is an `iTXt` chunk, no matter if its data is valid.
+    pub fn is_international_text_chunk(chunk: &Chunk) -> bool {
+        has_type(chunk, Self::CHUNK_TYPE)
+    }
+}
+
+impl TryFrom<&Chunk> for InternationalTextChunk {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        expect_type(chunk, InternationalTextChunk::CHUNK_TYPE)?;
+
+        let (keyword, rest) = split_keyword(chunk.data())?;
+        let (flag, method, rest) = match rest {
+            [flag, method, rest @ ..] => (*flag, *method, rest),
+            _ => {
+                return Err(PngError::InvalidText(String::from(
+                    "compression flag and method are missing",
+                )))
+            }
+        };
+        let compressed = match flag {
+            0 => false,
+            1 => true,
+            other => {
+                return Err(PngError::InvalidText(format!(
+                    "unknown compression flag {}",
+                    other
+                )))
+            }
+        };
+        check_compression_method(method)?;
+
+        let (language_tag, rest) = split_keyword(rest)?;
+        let (translated_keyword, text) = split_keyword(rest)?;
+        let text = if compressed {
+            inflate(text, MAX_TEXT_LENGTH)?
+        } else {
+            text.to_vec()
+        };
+
+        InternationalTextChunk::new(
+            &decode_latin1(keyword),
+            &String::from_utf8(language_tag.to_vec())?,
+            &String::from_utf8(translated_keyword.to_vec())?,
+            &String::from_utf8(text)?,
+            compressed,
+        )
+    }
+}
+
+/// Returns the text of a `tEXt`, `zTXt` or `iTXt` chunk, `None` for other chunk types.
+pub fn text_of(chunk: &Chunk) -> Option<Result<String, PngError>> {
+    if TextChunk::is_text_chunk(chunk) {
+        Some(TextChunk::try_from(chunk).map(|text| text.text))
+    } else if CompressedTextChunk::is_compressed_text_chunk(chunk) {
+        Some(CompressedTextChunk::try_from(chunk).map(|text| text.text))
+    } else if InternationalTextChunk::is_international_text_chunk(chunk) {
+        Some(InternationalTextChunk::try_from(chunk).map(|text| text.text))
+    } else {
+        None
+    }
+}
+
+/// Returns the keyword of a `tEXt`, `zTXt` or `iTXt` chunk without decoding its
+/// text, `None` for other chunk types and malformed chunks.
+pub fn keyword_of(chunk: &Chunk) -> Option<String> {
+    let is_text = TextChunk::is_text_chunk(chunk)
+        || CompressedTextChunk::is_compressed_text_chunk(chunk)
+        || InternationalTextChunk::is_international_text_chunk(chunk);
+    if !is_text {
+        return None;
+    }
+    let (keyword, _) = split_keyword(chunk.data()).ok()?;
+    Some(decode_latin1(keyword))
+}
+
+/// Checks keyword rules shared by all text chunks: 1-79 printable Latin-1
+/// characters, without leading, trailing or consecutive spaces.
+pub fn validate_keyword(keyword: &str) -> Result<(), PngError> {
+    let invalid = |reason: &str| Err(PngError::InvalidText(format!("keyword {}", reason)));
+
+    let len = keyword.chars().count();
+    if !(1..=79).contains(&len) {
+        return invalid("must have 1 to 79 characters");
+    }
+    if keyword
+        .chars()
+        .any(|c| !matches!(c as u32, 32..=126 | 161..=255))
+    {
+        return invalid("must only contain printable Latin-1 characters");
+    }
+    if keyword.starts_with(' ') || keyword.ends_with(' ') || keyword.contains("  ") {
+        return invalid("must not have leading, trailing or consecutive spaces");
+    }
+    Ok(())
+}
+
+fn validate_latin1_text(text: &str) -> Result<(), PngError> {
+    if text.chars().any(|c| c == '\0' || c as u32 > 0xff) {
+        return Err(PngError::InvalidText(String::from(
+            "text must be Latin-1 without NUL characters",
+        )));
+    }
+    Ok(())
+}
+
+/// Zlib is the only compression method defined for `zTXt` and `iTXt`.
+const COMPRESSION_METHOD_DEFLATE: u8 = 0;
+
+fn check_compression_method(method: u8) -> Result<(), PngError> {
+    if method != COMPRESSION_METHOD_DEFLATE {
+        return Err(PngError::InvalidText(format!(
+            "unknown compression method {}",
+            method
+        )));
+    }
+    Ok(())
+}
+
+fn has_type(chunk: &Chunk, chunk_type: &str) -> bool {
+    chunk.chunk_type().bytes() == chunk_type.as_bytes()
+}
+
+fn expect_type(chunk: &Chunk, chunk_type: &str) -> Result<(), PngError> {
+    if !has_type(chunk, chunk_type) {
+        return Err(PngError::InvalidText(format!(
+            "expected {} chunk, got {}",
+            chunk_type,
+            chunk.chunk_type()
+        )));
+    }
+    Ok(())
+}
+
+fn new_chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+    Chunk::new(
+        ChunkType::from_str(chunk_type).expect("text chunk types are valid"),
+        data,
+    )
+}
+
+/// Splits chunk data at the NUL byte terminating the keyword.
+pub(crate) fn split_keyword(data: &[u8]) -> Result<(&[u8], &[u8]), PngError> {
+    let separator = data
+        .iter()
+        .position(|b| *b == 0)
+        .ok_or(PngError::InvalidText(String::from(
+            "keyword is not NUL terminated",
+        )))?;
+    Ok((&data[..separator], &data[separator + 1..]))
+}
+
+pub(crate) fn decode_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| *b as char).collect()
+}
+
+/// Callers make sure `text` only holds characters up to U+00FF.
+pub(crate) fn encode_latin1(text: &str) -> Vec<u8> {
+    text.chars().map(|c| c as u8).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_chunk_roundtrip() {
+        let text = TextChunk::new("Author", "Zoë Ångström").unwrap();
+        let chunk = text.to_chunk();
+
+        assert_eq!(chunk.chunk_type().to_string(), "tEXt");
+        assert_eq!(chunk.data(), b"Author\0Zo\xeb \xc5ngstr\xf6m");
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_invalid_keywords() {
+        let long_keyword = "k".repeat(80);
+        for keyword in [
+            "",
+            " Title",
+            "Title ",
+            "Ti  tle",
+            "Tit\nle",
+            "Wörd€",
+            &long_keyword,
+        ] {
+            assert!(
+                matches!(
+                    TextChunk::new(keyword, "text"),
+                    Err(PngError::InvalidText(_))
+                ),
+                "{:?} should be invalid",
+                keyword
+            );
+        }
+        assert!(TextChunk::new(&"k".repeat(79), "text").is_ok());
+    }
+
+    #[test]
+    fn test_invalid_text() {
+        assert!(TextChunk::new("Comment", "emoji 🦀").is_err());
+        assert!(TextChunk::new("Comment", "nul\0").is_err());
+        assert!(TextChunk::new("Comment", "multi\nline").is_ok());
+    }
+
+    #[test]
+    fn test_text_chunk_from_bytes() {
+        let chunk = Chunk::new(
+            ChunkType::from_str("tEXt").unwrap(),
+            b"Title\0Photo\0".to_vec(),
+        );
+        assert!(TextChunk::try_from(&chunk).is_err());
+
+        let chunk = Chunk::new(ChunkType::from_str("tEXt").unwrap(), b"Title".to_vec());
+        assert!(TextChunk::try_from(&chunk).is_err());
+
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"Title\0x".to_vec());
+        assert!(TextChunk::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_compressed_text_roundtrip() {
+        let text = CompressedTextChunk::new("Comment", &"Zoë ".repeat(100)).unwrap();
+        let chunk = text.to_chunk();
+
+        assert_eq!(chunk.chunk_type().to_string(), "zTXt");
+        assert!(chunk.data().starts_with(b"Comment\0\0"));
+        assert!(chunk.data().len() < 100);
+        assert_eq!(CompressedTextChunk::try_from(&chunk).unwrap(), text);
+        assert_eq!(text_of(&chunk).unwrap().unwrap(), text.text());
+    }
+
+    #[test]
+    fn test_compressed_text_invalid_data() {
+        let chunk = Chunk::new(
+            ChunkType::from_str("zTXt").unwrap(),
+            b"Comment\0\x01x".to_vec(),
+        );
+        assert!(matches!(
+            CompressedTextChunk::try_from(&chunk),
+            Err(PngError::InvalidText(_))
+        ));
+
+        let chunk = Chunk::new(
+            ChunkType::from_str("zTXt").unwrap(),
+            b"Comment\0\0not zlib".to_vec(),
+        );
+        assert!(matches!(
+            CompressedTextChunk::try_from(&chunk),
+            Err(PngError::Decompression(_))
+        ));
+    }
+
+    #[test]
+    fn test_international_text_roundtrip() {
+        for compressed in [false, true] {
+            let text = InternationalTextChunk::new(
+                "Title",
+                "pl",
+                "Tytuł",
+                "Zażółć gęślą jaźń",
+                compressed,
+            )
+            .unwrap();
+            let chunk = text.to_chunk();
+
+            assert_eq!(chunk.chunk_type().to_string(), "iTXt");
+            assert_eq!(InternationalTextChunk::try_from(&chunk).unwrap(), text);
+            assert_eq!(text_of(&chunk).unwrap().unwrap(), "Zażółć gęślą jaźń");
+        }
+    }
+
+    #[test]
+    fn test_international_text_layout() {
+        let chunk = Chunk::new(
+            ChunkType::from_str("iTXt").unwrap(),
+            b"Title\0\0\0en-GB\0Title\0Hello".to_vec(),
+        );
+        let text = InternationalTextChunk::try_from(&chunk).unwrap();
+
+        assert_eq!(text.language_tag(), "en-GB");
+        assert_eq!(text.translated_keyword(), "Title");
+        assert_eq!(text.text(), "Hello");
+        assert!(!text.is_compressed());
+        assert_eq!(text.to_chunk().as_bytes(), chunk.as_bytes());
+    }
+
+    #[test]
+    fn test_international_text_invalid() {
+        assert!(InternationalTextChunk::new("Title", "en GB", "", "", false).is_err());
+
+        for data in [
+            &b"Title\0"[..],
+            b"Title\0\x02\0\0\0Hello",
+            b"Title\0\0\x01\0\0Hello",
+            b"Title\0\0\0en\0Hello",
+            b"Title\0\0\0\0\0\xff",
+        ] {
+            let chunk = Chunk::new(ChunkType::from_str("iTXt").unwrap(), data.to_vec());
+            assert!(
+                InternationalTextChunk::try_from(&chunk).is_err(),
+                "{:?} should be invalid",
+                data
+            );
+        }
+    }
+
+    #[test]
+    fn test_text_of_other_chunk() {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"Title\0x".to_vec());
+        assert!(text_of(&chunk).is_none());
+        assert!(keyword_of(&chunk).is_none());
+    }
+
+    #[test]
+    fn test_keyword_of() {
+        let text = TextChunk::new("Author", "Ferris").unwrap().to_chunk();
+        let compressed = CompressedTextChunk::new("Comment", "x").unwrap().to_chunk();
+        let international = InternationalTextChunk::new("Title", "", "", "x", true)
+            .unwrap()
+            .to_chunk();
+
+        assert_eq!(keyword_of(&text).as_deref(), Some("Author"));
+        assert_eq!(keyword_of(&compressed).as_deref(), Some("Comment"));
+        assert_eq!(keyword_of(&international).as_deref(), Some("Title"));
+        let malformed = Chunk::new(ChunkType::from_str("tEXt").unwrap(), b"Title".to_vec());
+        assert!(keyword_of(&malformed).is_none());
+    }
+}
@@ -0,0 +1,161 @@
+use std::io::{self, ErrorKind, Write};
+
+use crc::Digest;
+
+use crate::{
+    chunk::{Chunk, CRC_32_ISO},
+    chunk_type::ChunkType,
+    error::PngError,
+    png::Png,
+};
+
+/// Writes a png straight to any `Write` sink, one chunk at a time.
+///
+/// The signature is written in `new`. Chunk data is never copied, the CRC is
+/// computed while the bytes pass through.
+pub struct ChunkWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> ChunkWriter<W> {
+    pub fn new(mut writer: W) -> Result<ChunkWriter<W>, PngError> {
+        writer.write_all(Png::STANDARD_HEADER).map_err(io_error)?;
+        Ok(ChunkWriter { writer })
+    }
+
+    pub fn write_chunk(&mut self, chunk: &Chunk) -> Result<(), PngError> {
+        let mut data_writer = self.start_chunk(chunk.chunk_type(), chunk.length())?;
+        data_writer.write_all(chunk.data()).map_err(io_error)?;
+        data_writer.finish()
+    }
+
+    /// Starts a chunk of `length` data bytes. The data is written through the
+    /// returned writer, and `finish` appends the CRC.
+    pub fn start_chunk(
+        &mut self,
+        chunk_type: &ChunkType,
+        length: u32,
+    ) -> Result<ChunkDataWriter<'_, W>, PngError> {
+        self.writer
+            .write_all(&length.to_be_bytes())
+            .map_err(io_error)?;
+        self.writer
+            .write_all(&chunk_type.bytes())
+            .map_err(io_error)?;
+
+        let mut digest = CRC_32_ISO.digest();
+        digest.update(&chunk_type.bytes());
+
+        Ok(ChunkDataWriter {
+            writer: &mut self.writer,
+            digest,
+            remaining: length,
+        })
+    }
+
+    /// Flushes the sink and gives it back.
+    pub fn finish(mut self) -> Result<W, PngError> {
+        self.writer.flush().map_err(io_error)?;
+        Ok(self.writer)
+    }
+}
+
+/// Data part of a chunk started with `ChunkWriter::start_chunk`.
+pub struct ChunkDataWriter<'a, W: Write> {
+    writer: &'a mut W,
+    digest: Digest<'static, u32>,
+    remaining: u32,
+}
+
+impl<W: Write> ChunkDataWriter<'_, W> {
+    /// Writes the CRC, failing if less data was written than declared.
+    pub fn finish(self) -> Result<(), PngError> {
+        if self.remaining != 0 {
+            return Err(io_error(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "chunk data is {} bytes shorter than declared",
+                    self.remaining
+                ),
+            )));
+        }
+
+        let crc = self.digest.finalize();
+        self.writer.write_all(&crc.to_be_bytes()).map_err(io_error)
+    }
+}
+
+impl<W: Write> Write for ChunkDataWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() > self.remaining as usize {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "chunk data is longer than declared",
+            ));
+        }
+
+        let written = self.writer.write(buf)?;
+        self.digest.update(&buf[..written]);
+        self.remaining -= written as u32;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+fn io_error(source: io::Error) -> PngError {
+    PngError::Io {
+        path: String::from("<output>"),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const PNG_FILE: &[u8] = include_bytes!("../test.png");
+
+    #[test]
+    fn test_write_png() {
+        let png = Png::try_from(PNG_FILE).unwrap();
+        let mut writer = ChunkWriter::new(Vec::new()).unwrap();
+
+        for chunk in png.chunks() {
+            writer.write_chunk(chunk).unwrap();
+        }
+
+        assert_eq!(writer.finish().unwrap(), PNG_FILE);
+    }
+
+    #[test]
+    fn test_streamed_chunk_matches_chunk() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let message = "This is where your secret message will be!";
+        let mut writer = ChunkWriter::new(Vec::new()).unwrap();
+
+        let mut data_writer = writer.start_chunk(&chunk_type, 42).unwrap();
+        for word in message.split_inclusive(' ') {
+            data_writer.write_all(word.as_bytes()).unwrap();
+        }
+        data_writer.finish().unwrap();
+
+        let bytes = writer.finish().unwrap();
+        let expected = Chunk::new(chunk_type, message.as_bytes().to_vec());
+        assert_eq!(&bytes[8..], expected.as_bytes());
+    }
+
+    #[test]
+    fn test_declared_length_is_enforced() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let mut writer = ChunkWriter::new(Vec::new()).unwrap();
+
+        let mut data_writer = writer.start_chunk(&chunk_type, 2).unwrap();
+        assert!(data_writer.write_all(b"abc").is_err());
+        data_writer.write_all(b"a").unwrap();
+        assert!(matches!(data_writer.finish(), Err(PngError::Io { .. })));
+    }
+}